@@ -0,0 +1,40 @@
+use std::io;
+
+use crate::{
+    chai::TermSize,
+    components::{surface::Cell, text_block::CursorShape},
+    core::TermScreenCoords,
+};
+
+pub mod terminal;
+#[cfg(test)]
+pub mod test;
+
+pub use terminal::TerminalBackend;
+#[cfg(test)]
+pub use test::TestBackend;
+
+/// Where the editor draws its frames, a real terminal or an in-memory screen for tests
+pub trait Backend {
+    /// Prepares the screen before the first frame
+    fn setup(&mut self) -> io::Result<()>;
+
+    /// Gives the screen back as it was before `setup`
+    fn restore(&mut self) -> io::Result<()>;
+
+    /// Returns the size of the screen in columns and rows
+    fn get_size(&self) -> io::Result<TermSize>;
+
+    /// Blanks the whole screen
+    fn clear(&mut self) -> io::Result<()>;
+
+    /// Draws `cells` at their `(x, y)` position, leaving the other cells untouched
+    fn draw<'a>(&mut self, cells: impl Iterator<Item = (u16, u16, &'a Cell)>) -> io::Result<()>;
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> io::Result<()>;
+
+    fn move_cursor(&mut self, position: TermScreenCoords) -> io::Result<()>;
+
+    /// Sends everything drawn since the last flush to the screen
+    fn flush(&mut self) -> io::Result<()>;
+}
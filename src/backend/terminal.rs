@@ -0,0 +1,121 @@
+use std::io::{self, Stdout, Write};
+
+use crossterm::{
+    cursor, execute, queue,
+    style::{Attribute, Print, SetAttribute, SetStyle},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, window_size, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    backend::Backend,
+    chai::TermSize,
+    components::{surface::Cell, text_block::CursorShape},
+    core::TermScreenCoords,
+};
+
+/// Draws to the terminal through stdout, in raw mode on the alternate screen
+pub struct TerminalBackend {
+    writer: Stdout,
+}
+
+impl TerminalBackend {
+    pub fn new() -> Self {
+        Self {
+            writer: io::stdout(),
+        }
+    }
+}
+
+impl Backend for TerminalBackend {
+    fn setup(&mut self) -> io::Result<()> {
+        execute!(self.writer, EnterAlternateScreen)?;
+        enable_raw_mode()?;
+
+        execute!(self.writer, Clear(ClearType::All))?;
+
+        Ok(())
+    }
+
+    fn restore(&mut self) -> io::Result<()> {
+        execute!(
+            self.writer,
+            cursor::SetCursorStyle::DefaultUserShape,
+            LeaveAlternateScreen
+        )?;
+        disable_raw_mode()?;
+
+        Ok(())
+    }
+
+    fn get_size(&self) -> io::Result<TermSize> {
+        let size = window_size()?;
+
+        Ok(TermSize {
+            width: size.columns,
+            height: size.rows,
+        })
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        queue!(self.writer, Clear(ClearType::All))
+    }
+
+    fn draw<'a>(&mut self, cells: impl Iterator<Item = (u16, u16, &'a Cell)>) -> io::Result<()> {
+        let mut terminal_cursor = None;
+        let mut style = None;
+
+        for (x, y, cell) in cells {
+            // The cells covered by a wide grapheme are printed along with it
+            if cell.symbol.is_empty() {
+                continue;
+            }
+
+            // Consecutive cells are printed without moving the cursor in between
+            if terminal_cursor != Some((x, y)) {
+                queue!(self.writer, cursor::MoveTo(x, y))?;
+            }
+
+            if style != Some(cell.style) {
+                queue!(
+                    self.writer,
+                    SetAttribute(Attribute::Reset),
+                    SetStyle(cell.style)
+                )?;
+
+                style = Some(cell.style);
+            }
+
+            queue!(self.writer, Print(&cell.symbol))?;
+
+            terminal_cursor = Some((x + cell.symbol.width().max(1) as u16, y));
+        }
+
+        if style.is_some() {
+            queue!(self.writer, SetAttribute(Attribute::Reset))?;
+        }
+
+        Ok(())
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> io::Result<()> {
+        let style = match shape {
+            CursorShape::Block => cursor::SetCursorStyle::SteadyBlock,
+            CursorShape::Bar => cursor::SetCursorStyle::SteadyBar,
+            CursorShape::Underline => cursor::SetCursorStyle::SteadyUnderScore,
+        };
+
+        queue!(self.writer, style)
+    }
+
+    fn move_cursor(&mut self, position: TermScreenCoords) -> io::Result<()> {
+        queue!(self.writer, cursor::MoveTo(position.x, position.y))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,95 @@
+use std::io;
+
+use crate::{
+    backend::Backend,
+    chai::TermSize,
+    components::{surface::Cell, text_block::CursorShape},
+    core::TermScreenCoords,
+};
+
+/// Keeps the screen in memory, so tests can run the editor and look at what it shows
+pub struct TestBackend {
+    size: TermSize,
+    lines: Vec<Vec<String>>,
+    cursor: TermScreenCoords,
+    cursor_shape: Option<CursorShape>,
+}
+
+impl TestBackend {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            size: TermSize { width, height },
+            lines: vec![vec![" ".to_owned(); width as usize]; height as usize],
+            cursor: TermScreenCoords { x: 0, y: 0 },
+            cursor_shape: None,
+        }
+    }
+
+    /// Returns the rows of the screen, without their trailing spaces
+    pub fn get_lines(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .map(|line| line.concat().trim_end().to_owned())
+            .collect()
+    }
+
+    pub fn get_cursor(&self) -> (u16, u16) {
+        (self.cursor.x, self.cursor.y)
+    }
+
+    pub fn get_cursor_shape(&self) -> Option<CursorShape> {
+        self.cursor_shape
+    }
+}
+
+impl Backend for TestBackend {
+    fn setup(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn restore(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn get_size(&self) -> io::Result<TermSize> {
+        Ok(self.size)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        for line in &mut self.lines {
+            line.fill(" ".to_owned());
+        }
+
+        Ok(())
+    }
+
+    fn draw<'a>(&mut self, cells: impl Iterator<Item = (u16, u16, &'a Cell)>) -> io::Result<()> {
+        for (x, y, cell) in cells {
+            if let Some(symbol) = self
+                .lines
+                .get_mut(y as usize)
+                .and_then(|line| line.get_mut(x as usize))
+            {
+                *symbol = cell.symbol.clone();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> io::Result<()> {
+        self.cursor_shape = Some(shape);
+
+        Ok(())
+    }
+
+    fn move_cursor(&mut self, position: TermScreenCoords) -> io::Result<()> {
+        self.cursor = position;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
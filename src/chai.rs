@@ -1,130 +1,237 @@
-use crossterm::{
-    cursor,
-    event::{Event, KeyCode, KeyEvent, KeyModifiers},
-    execute, queue,
-    terminal::{
-        disable_raw_mode, enable_raw_mode, window_size, Clear, ClearType, EnterAlternateScreen,
-        LeaveAlternateScreen,
-    },
-};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use futures_core::Stream;
 use futures_util::StreamExt;
+use regex::Regex;
 
 use crate::{
-    components::Pane,
-    core::{document::Document, TermScreenCoords},
-};
-use std::{
-    io::{self, Write},
-    process::exit,
+    backend::Backend,
+    cli::{Args, StartPosition},
+    components::{
+        command_line::{CommandLineEvent, Message},
+        pane::SplitDirection,
+        text_block::{CursorShape, Mode},
+        CommandLine, Pane, StatusLine, Surface, TUIComponent,
+    },
+    core::{
+        command::CommandError,
+        document::{DocumentHandle, WriteStats},
+        encoding,
+        options::{OptionName, SetAction, SetArgument},
+        Command, Document, TermScreenCoords,
+    },
 };
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+/// How long a partly typed normal mode command waits for its next key, like vim's `timeoutlen`
+const PENDING_KEYS_TIMEOUT: Duration = Duration::from_millis(1000);
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct TermSize {
     pub width: u16,
     pub height: u16,
 }
 
-pub struct Chai {
-    pub writer: io::Stdout,
+pub struct Chai<B: Backend> {
+    pub backend: B,
     pub panes: Vec<Pane>,
     pub active_pane_index: usize,
     pub window_size: TermSize,
-    pub documents: Vec<Document>,
+    pub documents: Vec<DocumentHandle>,
+    pub command_line: CommandLine,
+    pub status_line: StatusLine,
+    confirm_quit: bool,
+    window_command_pending: bool,
+    cursor_shape: Option<CursorShape>,
+    previous_frame: Option<Surface>,
+    /// Whether the screen was set up and still has to be restored
+    screen_set_up: bool,
 }
 
-impl Drop for Chai {
+impl<B: Backend> Drop for Chai<B> {
     fn drop(&mut self) {
-        self.restore_terminal().unwrap();
+        // Panicking in drop could abort while already unwinding
+        let _ = self.restore_screen();
     }
 }
 
-impl Chai {
-    pub async fn new(file_path: Option<String>, window_size: TermSize) -> anyhow::Result<Self> {
-        let default_doc = Document::new(file_path).await?;
-        let default_content = default_doc.get_content().clone();
-        let documents = vec![default_doc];
+impl<B: Backend> Chai<B> {
+    pub async fn new(args: Args, backend: B) -> anyhow::Result<Self> {
+        let size = backend.get_size()?;
+        let window_size = TermSize {
+            width: size.width.saturating_sub(1),
+            height: size.height,
+        };
+
+        let mut documents = vec![];
+
+        for file in args.files {
+            let mut document = Document::new(Some(file.file_path)).await?;
+
+            if let Some(cursor) = file.cursor {
+                document.set_last_cursor(cursor);
+            }
+
+            documents.push(document);
+        }
+
+        if documents.is_empty() {
+            documents.push(Document::empty());
+        }
+
+        let documents = documents
+            .into_iter()
+            .map(|mut document| {
+                if args.read_only {
+                    document.set_read_only();
+                }
+
+                Rc::new(RefCell::new(document))
+            })
+            .collect::<Vec<_>>();
+
+        let default_doc = documents[0].clone();
+
+        let mut command_line = CommandLine::new();
+
+        if let Some(message) = get_flags_message(&default_doc.borrow()) {
+            command_line.set_message(message);
+        }
+
+        let cursor = default_doc.borrow().get_last_cursor();
 
-        let editor = Chai {
-            writer: io::stdout(),
+        let mut editor = Chai {
+            backend,
             active_pane_index: 0,
             window_size,
             panes: vec![Pane::new(
-                default_content,
+                default_doc,
                 TermSize {
                     width: window_size.width,
-                    height: window_size.height - 1,
+                    height: window_size.height.saturating_sub(1),
                 },
                 TermScreenCoords { x: 0, y: 0 },
                 None,
             )],
             documents,
+            command_line,
+            status_line: StatusLine::new(),
+            confirm_quit: false,
+            window_command_pending: false,
+            cursor_shape: None,
+            previous_frame: None,
+            screen_set_up: false,
         };
 
+        let view = editor.get_current_pane_mut()?.get_current_view_mut()?;
+
+        view.set_cursor(cursor)?;
+
+        if let Some(start) = args.start {
+            if let Err(e) = editor.move_to_start(start) {
+                editor
+                    .command_line
+                    .set_message(Message::Error(e.to_string()));
+            }
+        }
+
+        for command in args.commands {
+            editor.run_command(&command).await;
+        }
+
         Ok(editor)
     }
 
-    pub async fn start<S>(mut self, read_stream: &mut S) -> anyhow::Result<()>
+    /// Moves the cursor of the active view to the position requested on the command line
+    fn move_to_start(&mut self, start: StartPosition) -> anyhow::Result<()> {
+        let document = self.get_active_document()?;
+
+        let cursor = match start {
+            StartPosition::Line(line) => (0, line.saturating_sub(1)).into(),
+            StartPosition::LastLine => (0, usize::MAX).into(),
+            StartPosition::Pattern(pattern) => {
+                let regex = Regex::new(&pattern)?;
+
+                document
+                    .borrow()
+                    .get_content()
+                    .find(&regex)
+                    .ok_or(anyhow::anyhow!("Pattern not found: {}", pattern))?
+            }
+        };
+
+        self.get_current_pane_mut()?
+            .get_current_view_mut()?
+            .set_cursor(cursor)
+    }
+
+    /// Runs the editor until it is closed or `read_stream` ends
+    pub async fn start<S>(&mut self, read_stream: &mut S) -> anyhow::Result<()>
     where
         S: Stream<Item = std::io::Result<crossterm::event::Event>> + Unpin,
     {
-        self.setup_terminal()?;
+        // A `-c` command like `q` can close the editor before it is shown
+        if self.should_close() {
+            return Ok(());
+        }
 
-        let size = window_size()?;
+        // Set first so a setup that fails halfway is still undone
+        self.screen_set_up = true;
+        self.backend.setup()?;
 
-        self.window_size = TermSize {
-            width: size.columns.saturating_sub(1),
-            height: size.rows,
-        };
+        let size = self.backend.get_size()?;
 
-        self.clear()?;
+        self.resize(size.width, size.height)?;
         self.render()?;
-
-        let term_cursor_pos = self
-            .get_active_pane()?
-            .get_active_view()?
-            .get_cursor_term_pos()?;
-
-        queue!(
-            self.writer,
-            cursor::MoveTo(term_cursor_pos.x, term_cursor_pos.y)
-        )?;
-        self.writer.flush()?;
+        self.move_cursor()?;
+        self.backend.flush()?;
 
         let result = self.run_loop(read_stream).await;
 
-        self.restore_terminal()?;
+        self.restore_screen()?;
 
         result
     }
 
+    /// Restores the screen if it was set up, only once
+    fn restore_screen(&mut self) -> std::io::Result<()> {
+        if !std::mem::take(&mut self.screen_set_up) {
+            return Ok(());
+        }
+
+        self.backend.restore()
+    }
+
     async fn run_loop<S>(&mut self, read_stream: &mut S) -> anyhow::Result<()>
     where
         S: Stream<Item = std::io::Result<crossterm::event::Event>> + Unpin,
     {
         while !self.should_close() {
-            let Some(event) = read_stream.next().await else {
-                continue;
+            let event = match self
+                .get_active_pane()?
+                .get_active_view()?
+                .has_pending_keys()
+            {
+                true => tokio::time::timeout(PENDING_KEYS_TIMEOUT, read_stream.next()).await,
+                false => Ok(read_stream.next().await),
             };
 
-            let event = event?;
+            match event {
+                Ok(Some(event)) => self.handle_event(event?).await?,
+                Ok(None) => break,
+                // The keys typed so far are dropped when the next one takes too long
+                Err(_) => self
+                    .get_current_pane_mut()?
+                    .get_current_view_mut()?
+                    .cancel_pending_keys(),
+            }
 
-            self.clear()?;
-            self.handle_event(event)?;
+            if self.should_close() {
+                break;
+            }
 
             self.render()?;
-
-            let term_cursor_pos = self
-                .get_active_pane()?
-                .get_active_view()?
-                .get_cursor_term_pos()?;
-
-            queue!(
-                self.writer,
-                cursor::MoveTo(term_cursor_pos.x, term_cursor_pos.y)
-            )?;
-
-            self.writer.flush()?;
+            self.move_cursor()?;
+            self.backend.flush()?;
         }
 
         Ok(())
@@ -142,61 +249,1052 @@ impl Chai {
             .ok_or(anyhow::anyhow!("No pane found"))
     }
 
-    fn clear(&mut self) -> io::Result<()> {
-        queue!(self.writer, Clear(ClearType::All))
+    /// Places the terminal cursor for the next frame, changing its shape when the mode changed
+    fn move_cursor(&mut self) -> anyhow::Result<()> {
+        let term_cursor_pos = self.get_cursor_term_pos()?;
+
+        let cursor_shape = match self.command_line.is_active() {
+            true => CursorShape::Bar,
+            false => self
+                .get_active_pane()?
+                .get_active_view()?
+                .get_cursor_shape(),
+        };
+
+        if self.cursor_shape != Some(cursor_shape) {
+            self.backend.set_cursor_shape(cursor_shape)?;
+            self.cursor_shape = Some(cursor_shape);
+        }
+
+        self.backend.move_cursor(term_cursor_pos)?;
+
+        Ok(())
     }
 
+    /// Lays the panes out again for a terminal of `columns` by `rows`, scaling every view so
+    /// they keep their proportions
+    fn resize(&mut self, columns: u16, rows: u16) -> anyhow::Result<()> {
+        let old_size = self.window_size;
+
+        // What the terminal shows after a resize is unknown, so the next frame is drawn in full
+        self.previous_frame = None;
+        self.backend.clear()?;
+
+        self.window_size = TermSize {
+            width: columns.saturating_sub(1),
+            height: rows,
+        };
+
+        // The bottom row is left to the status line and the command line
+        let get_pane_size = |size: TermSize| TermSize {
+            width: size.width,
+            height: size.height.saturating_sub(1),
+        };
+
+        for pane in &mut self.panes {
+            pane.resize(get_pane_size(old_size), get_pane_size(self.window_size));
+
+            for view in pane.get_views_mut() {
+                view.scroll(self.window_size)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_cursor_term_pos(&self) -> anyhow::Result<TermScreenCoords> {
+        if self.command_line.is_active() {
+            return self.command_line.get_cursor_term_pos(self.window_size);
+        }
+
+        self.get_active_pane()?
+            .get_active_view()?
+            .get_cursor_term_pos()
+    }
+
+    /// Renders every component into a new frame and sends the cells that changed since the
+    /// previous frame to the terminal
     fn render(&mut self) -> anyhow::Result<()> {
+        let mut frame = Surface::new(self.window_size);
+
         for window in self.panes.iter_mut() {
-            window.render(&mut self.writer, self.window_size)?;
+            window.render(&mut frame)?;
+        }
+
+        // The status line shares the bottom row with the command line and gives way to it
+        if !self.command_line.is_visible() {
+            if let Some(pane) = self.panes.get(self.active_pane_index) {
+                self.status_line.update(pane.get_active_view()?);
+                self.status_line.render(&mut frame)?;
+            }
         }
 
+        self.command_line.render(&mut frame)?;
+
+        self.backend
+            .draw(frame.diff(self.previous_frame.as_ref()))?;
+        self.previous_frame = Some(frame);
+
         Ok(())
     }
 
-    fn setup_terminal(&mut self) -> io::Result<()> {
-        execute!(self.writer, EnterAlternateScreen)?;
-        enable_raw_mode()?;
+    async fn handle_event(&mut self, event: Event) -> anyhow::Result<()> {
+        // Resizes apply whatever is focused, the command line included
+        if let Event::Resize(width, height) = event {
+            return self.resize(width, height);
+        }
+
+        if self.command_line.is_active() {
+            if let Event::Key(event) = event {
+                self.handle_command_line_key(&event).await?;
+            }
+
+            return Ok(());
+        }
 
-        execute!(self.writer, Clear(ClearType::All))?;
+        if let Event::Key(_) = event {
+            self.command_line.clear_message();
+        }
+
+        let handled = match event {
+            Event::FocusGained => false,
+            Event::FocusLost => false,
+            Event::Key(event) => self.handle_key(event)?,
+            Event::Mouse(_event) => false,
+            Event::Paste(ref _data) => false,
+            Event::Resize(_width, _height) => false,
+        };
+
+        if handled || self.should_close() {
+            return Ok(());
+        }
+
+        let view = self.get_current_pane_mut()?.get_current_view_mut()?;
+
+        // Editing errors, such as modifying a read-only document, are reported instead of
+        // closing the editor
+        if let Err(error) = view.update(&event) {
+            self.command_line
+                .set_message(Message::Error(error.to_string()));
+
+            return Ok(());
+        }
+
+        if view.get_mode() == Mode::Command {
+            self.command_line.open();
+        }
 
         Ok(())
     }
 
-    pub fn restore_terminal(&mut self) -> io::Result<()> {
-        execute!(self.writer, LeaveAlternateScreen)?;
-        disable_raw_mode()?;
+    async fn handle_command_line_key(&mut self, event: &KeyEvent) -> anyhow::Result<()> {
+        let input = match self.command_line.handle_key(event) {
+            CommandLineEvent::None => return Ok(()),
+            CommandLineEvent::Cancel => None,
+            CommandLineEvent::Submit(input) => Some(input),
+        };
+
+        self.get_current_pane_mut()?
+            .get_current_view_mut()?
+            .set_mode(Mode::Normal);
+
+        if let Some(input) = input {
+            self.run_command(&input).await;
+        }
 
         Ok(())
     }
 
-    fn handle_event(&mut self, event: Event) -> anyhow::Result<()> {
-        match event {
-            Event::FocusGained => {}
-            Event::FocusLost => {}
-            Event::Key(event) => self.handle_key(event)?,
-            Event::Mouse(_event) => {}
-            Event::Paste(ref _data) => {}
-            Event::Resize(_width, _height) => {}
+    /// Parses and executes an ex command, reporting errors in the command line
+    async fn run_command(&mut self, input: &str) {
+        let result = match input.parse::<Command>() {
+            Ok(command) => self.execute_command(command).await,
+            Err(CommandError::Empty) => Ok(()),
+            Err(e) => Err(e.into()),
         };
 
-        let view = self.get_current_pane_mut()?.get_current_view_mut()?;
+        if let Err(e) = result {
+            self.command_line.set_message(Message::Error(e.to_string()));
+        }
+    }
+
+    async fn execute_command(&mut self, command: Command) -> anyhow::Result<()> {
+        match command {
+            Command::Write {
+                file_path,
+                create_dirs,
+            } => {
+                self.write_document(file_path.as_deref(), create_dirs, false)
+                    .await;
+            }
+            Command::WriteQuit {
+                file_path,
+                create_dirs,
+            } => {
+                if self
+                    .write_document(file_path.as_deref(), create_dirs, false)
+                    .await
+                {
+                    self.quit(false);
+                }
+            }
+            Command::SaveAs {
+                file_path,
+                create_dirs,
+            } => {
+                // The document only moves to the new path once it was written there
+                self.write_document(Some(&file_path), create_dirs, true)
+                    .await;
+            }
+            Command::Set { arguments } => {
+                for argument in arguments {
+                    self.set_option(argument)?;
+                }
+            }
+            Command::Split => self
+                .get_current_pane_mut()?
+                .split(SplitDirection::Horizontal)?,
+            Command::VerticalSplit => self
+                .get_current_pane_mut()?
+                .split(SplitDirection::Vertical)?,
+            Command::Quit { force } => self.quit(force),
+            Command::QuitAll { force } => self.quit_all(force),
+            Command::ListBuffers => self.list_buffers()?,
+            Command::NextBuffer => self.cycle_buffer(1)?,
+            Command::PreviousBuffer => self.cycle_buffer(-1)?,
+            Command::Buffer { target } => {
+                let index = self.find_buffer(&target)?;
+
+                self.show_buffer(index)?;
+            }
+            Command::DeleteBuffer { target, force } => {
+                let index = match target {
+                    Some(target) => self.find_buffer(&target)?,
+                    None => self.get_active_buffer_index()?,
+                };
+
+                self.delete_buffer(index, force)?;
+            }
+        };
+
+        Ok(())
+    }
+
+    fn set_option(&mut self, argument: SetArgument) -> anyhow::Result<()> {
+        let document = self.get_active_document()?;
+
+        match (argument.option, argument.action) {
+            (OptionName::FileFormat, SetAction::Assign(value)) => {
+                document.borrow_mut().set_line_ending(value.parse()?);
+            }
+            (OptionName::FileFormat, _) => {
+                let line_ending = document.borrow().get_format().line_ending;
+
+                self.command_line.set_message(Message::Info(format!(
+                    "{}={}",
+                    argument.option.name(),
+                    line_ending
+                )));
+            }
+            (OptionName::FileEncoding, SetAction::Assign(value)) => {
+                let encoding = encoding::find_encoding(&value)
+                    .ok_or(CommandError::InvalidArgument(value.clone()))?;
+
+                document.borrow_mut().set_encoding(encoding);
+            }
+            (OptionName::StatusLine, SetAction::Assign(value)) => {
+                self.status_line.set_format(value);
+            }
+            (OptionName::StatusLine, _) => {
+                self.command_line.set_message(Message::Info(format!(
+                    "{}={}",
+                    argument.option.name(),
+                    self.status_line.get_format()
+                )));
+            }
+            (OptionName::FileEncoding, _) => {
+                let encoding = document.borrow().get_format().encoding;
+
+                self.command_line.set_message(Message::Info(format!(
+                    "{}={}",
+                    argument.option.name(),
+                    encoding::get_name(encoding)
+                )));
+            }
+            (OptionName::TabStop, SetAction::Assign(value)) => {
+                let tab_stop = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|tab_stop| (1..=100).contains(tab_stop))
+                    .ok_or(CommandError::InvalidArgument(value.clone()))?;
+
+                document.borrow_mut().set_tab_stop(tab_stop);
+            }
+            (OptionName::TabStop, _) => {
+                let tab_stop = document.borrow().get_tab_stop();
+
+                self.command_line.set_message(Message::Info(format!(
+                    "{}={}",
+                    argument.option.name(),
+                    tab_stop
+                )));
+            }
+            (OptionName::ShowBreak, SetAction::Assign(value)) => {
+                let view = self.get_current_pane_mut()?.get_current_view_mut()?;
+                let mut wrap = view.get_wrap().clone();
+
+                wrap.show_break = value;
+                view.set_wrap(wrap);
+            }
+            (OptionName::ShowBreak, _) => {
+                let view = self.get_active_pane()?.get_active_view()?;
+                let message = format!("{}={}", argument.option.name(), view.get_wrap().show_break);
+
+                self.command_line.set_message(Message::Info(message));
+            }
+            (
+                OptionName::Number
+                | OptionName::RelativeNumber
+                | OptionName::Wrap
+                | OptionName::LineBreak,
+                action,
+            ) => {
+                let view = self.get_current_pane_mut()?.get_current_view_mut()?;
+                let mut line_numbers = view.get_line_numbers();
+                let mut wrap = view.get_wrap().clone();
 
-        self.documents.first_mut().unwrap().content = view.update(&event)?;
+                let enabled = match argument.option {
+                    OptionName::Number => &mut line_numbers.number,
+                    OptionName::RelativeNumber => &mut line_numbers.relative_number,
+                    OptionName::Wrap => &mut wrap.enabled,
+                    _ => &mut wrap.line_break,
+                };
+
+                let query = match action {
+                    SetAction::Enable => {
+                        *enabled = true;
+
+                        None
+                    }
+                    SetAction::Disable => {
+                        *enabled = false;
+
+                        None
+                    }
+                    SetAction::Toggle => {
+                        *enabled = !*enabled;
+
+                        None
+                    }
+                    SetAction::Query | SetAction::Assign(_) => Some(*enabled),
+                };
+
+                view.set_line_numbers(line_numbers);
+                view.set_wrap(wrap);
+
+                if let Some(enabled) = query {
+                    let prefix = if enabled { "" } else { "no" };
+
+                    self.command_line.set_message(Message::Info(format!(
+                        "{}{}",
+                        prefix,
+                        argument.option.name()
+                    )));
+                }
+            }
+        };
 
         Ok(())
     }
 
-    fn handle_key(&mut self, event: KeyEvent) -> anyhow::Result<()> {
-        if let (KeyModifiers::CONTROL, KeyCode::Char('c')) = (event.modifiers, event.code) {
-            self.restore_terminal()?;
-            exit(0);
+    /// Writes the current document, reporting the result in the command line.
+    /// Returns whether the write succeeded.
+    async fn write_document(
+        &mut self,
+        file_path: Option<&str>,
+        create_dirs: bool,
+        rename: bool,
+    ) -> bool {
+        let result = self
+            .write_active_document(file_path, create_dirs, rename)
+            .await;
+
+        match result {
+            Ok(stats) => {
+                self.command_line.set_message(Message::Info(format!(
+                    "\"{}\" {}L, {}B written",
+                    stats.file_path, stats.lines, stats.bytes
+                )));
+
+                true
+            }
+            Err(e) => {
+                self.command_line
+                    .set_message(Message::Error(format!("Error writing file: {}", e)));
+
+                false
+            }
+        }
+    }
+
+    /// Handles the keys that act on the editor rather than the active view. Returns whether the
+    /// key was consumed.
+    fn handle_key(&mut self, event: KeyEvent) -> anyhow::Result<bool> {
+        let confirm_quit = std::mem::take(&mut self.confirm_quit);
+
+        if std::mem::take(&mut self.window_command_pending) {
+            self.handle_window_command(event)?;
+
+            return Ok(true);
+        }
+
+        let mode = self.get_active_pane()?.get_active_view()?.get_mode();
+
+        match (event.modifiers, event.code, mode) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c'), _) => {
+                match self.get_dirty_document() {
+                    Some(_) if !confirm_quit => {
+                        self.confirm_quit = true;
+                        self.command_line.set_message(Message::Error(
+                            "Unsaved changes, press Ctrl-C again to quit without saving".to_owned(),
+                        ));
+                    }
+                    _ => self.quit_all(true),
+                };
+
+                Ok(true)
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('w'), Mode::Normal) => {
+                self.window_command_pending = true;
+
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Handles the key following `Ctrl-W`
+    fn handle_window_command(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        let pane = self.get_current_pane_mut()?;
+
+        let result = match (event.modifiers, event.code) {
+            (KeyModifiers::NONE, KeyCode::Char('w'))
+            | (KeyModifiers::CONTROL, KeyCode::Char('w')) => {
+                pane.focus_view(1);
+
+                Ok(())
+            }
+            (KeyModifiers::SHIFT, KeyCode::Char('W')) => {
+                pane.focus_view(-1);
+
+                Ok(())
+            }
+            (KeyModifiers::NONE, KeyCode::Char('s')) => pane.split(SplitDirection::Horizontal),
+            (KeyModifiers::NONE, KeyCode::Char('v')) => pane.split(SplitDirection::Vertical),
+            (KeyModifiers::NONE, KeyCode::Char('q')) => {
+                self.quit(false);
+
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+
+        if let Err(e) = result {
+            self.command_line.set_message(Message::Error(e.to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn write_active_document(
+        &mut self,
+        file_path: Option<&str>,
+        create_dirs: bool,
+        rename: bool,
+    ) -> anyhow::Result<WriteStats> {
+        let document = self.get_active_document()?;
+        let job = document
+            .borrow()
+            .prepare_write(file_path, create_dirs, rename)?;
+        let stats = job.run().await?;
+
+        document.borrow_mut().mark_written(&stats);
+
+        Ok(stats)
+    }
+
+    fn get_active_document(&self) -> anyhow::Result<DocumentHandle> {
+        Ok(self
+            .get_active_pane()?
+            .get_active_view()?
+            .get_document()
+            .clone())
+    }
+
+    /// Returns the index in the buffer list of the document shown in the active view
+    fn get_active_buffer_index(&self) -> anyhow::Result<usize> {
+        let document = self.get_active_document()?;
+
+        self.documents
+            .iter()
+            .position(|d| Rc::ptr_eq(d, &document))
+            .ok_or(anyhow::anyhow!("No buffer found"))
+    }
+
+    /// Finds a buffer by its number, as shown by `:ls`, or by a part of its file path that only
+    /// one buffer matches
+    fn find_buffer(&self, target: &str) -> anyhow::Result<usize> {
+        if let Ok(number) = target.parse::<usize>() {
+            return match number {
+                1.. if number <= self.documents.len() => Ok(number - 1),
+                _ => Err(anyhow::anyhow!("Buffer {} does not exist", number)),
+            };
+        }
+
+        let get_file_path = |document: &DocumentHandle| {
+            document
+                .borrow()
+                .get_file_path()
+                .map(str::to_owned)
+                .unwrap_or_default()
         };
 
+        if let Some(index) = self
+            .documents
+            .iter()
+            .position(|document| get_file_path(document) == target)
+        {
+            return Ok(index);
+        }
+
+        let matches = self
+            .documents
+            .iter()
+            .enumerate()
+            .filter(|(_, document)| get_file_path(document).contains(target))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        match matches[..] {
+            [index] => Ok(index),
+            [] => Err(anyhow::anyhow!("No matching buffer for {}", target)),
+            _ => Err(anyhow::anyhow!("More than one match for {}", target)),
+        }
+    }
+
+    /// Shows the buffer list in the command line. Like vim, `%` marks the buffer of the active
+    /// view, `a` the buffers shown in a view, `=` the read-only buffers and `+` the modified ones.
+    fn list_buffers(&mut self) -> anyhow::Result<()> {
+        let active_index = self.get_active_buffer_index()?;
+
+        let lines = self
+            .documents
+            .iter()
+            .enumerate()
+            .map(|(index, document)| {
+                let is_visible = self.panes.iter().any(|pane| pane.shows_document(document));
+                let document = document.borrow();
+
+                format!(
+                    "{:>3} {}{}{}{} \"{}\"",
+                    index + 1,
+                    if index == active_index { '%' } else { ' ' },
+                    if is_visible { 'a' } else { 'h' },
+                    if document.is_read_only() { '=' } else { ' ' },
+                    if document.is_dirty() { '+' } else { ' ' },
+                    document.get_file_path().unwrap_or("[No Name]")
+                )
+            })
+            .collect::<Vec<_>>();
+
+        self.command_line
+            .set_message(Message::Info(lines.join("\n")));
+
+        Ok(())
+    }
+
+    /// Shows the buffer `steps` positions away from the active one in the buffer list, wrapping
+    /// around at both ends
+    fn cycle_buffer(&mut self, steps: isize) -> anyhow::Result<()> {
+        let len = self.documents.len() as isize;
+        let index = (self.get_active_buffer_index()? as isize + steps).rem_euclid(len);
+
+        self.show_buffer(index as usize)
+    }
+
+    fn show_buffer(&mut self, index: usize) -> anyhow::Result<()> {
+        let document = self
+            .documents
+            .get(index)
+            .ok_or(anyhow::anyhow!("Buffer {} does not exist", index + 1))?
+            .clone();
+
+        self.get_current_pane_mut()?
+            .get_current_view_mut()?
+            .set_document(document.clone());
+
+        if let Some(message) = get_flags_message(&document.borrow()) {
+            self.command_line.set_message(message);
+        }
+
         Ok(())
     }
 
+    /// Unloads a buffer, refusing to discard its unsaved changes unless `force` is set. Views
+    /// showing it switch to the next buffer, or to a new empty one when it was the last.
+    fn delete_buffer(&mut self, index: usize, force: bool) -> anyhow::Result<()> {
+        let document = self.documents[index].clone();
+
+        if document.borrow().is_dirty() && !force {
+            anyhow::bail!(
+                "No write since last change for buffer \"{}\" (add ! to override)",
+                document.borrow().get_file_path().unwrap_or("[No Name]")
+            );
+        }
+
+        self.documents.remove(index);
+
+        if self.documents.is_empty() {
+            self.documents
+                .push(Rc::new(RefCell::new(Document::empty())));
+        }
+
+        let replacement = self.documents[index.min(self.documents.len() - 1)].clone();
+
+        for pane in &mut self.panes {
+            for view in pane.get_views_mut() {
+                if Rc::ptr_eq(view.get_document(), &document) {
+                    view.set_document(replacement.clone());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_dirty_document(&self) -> Option<DocumentHandle> {
+        self.documents
+            .iter()
+            .find(|document| document.borrow().is_dirty())
+            .cloned()
+    }
+
+    /// Refuses to continue when there are unsaved changes, reporting the first dirty document.
+    /// Returns whether it is safe to discard the documents.
+    fn check_unsaved_changes(&mut self) -> bool {
+        let Some(document) = self.get_dirty_document() else {
+            return true;
+        };
+
+        let message = format!(
+            "No write since last change for buffer \"{}\" (add ! to override)",
+            document.borrow().get_file_path().unwrap_or("[No Name]")
+        );
+
+        self.command_line.set_message(Message::Error(message));
+
+        false
+    }
+
+    /// Closes the active view, and its pane along with it when it was the last one. Closing the
+    /// last pane is refused while there are unsaved changes unless `force` is set.
+    fn quit(&mut self, force: bool) {
+        if let Ok(pane) = self.get_current_pane_mut() {
+            if pane.close_active_view() {
+                return;
+            }
+        }
+
+        if self.panes.len() <= 1 && !force && !self.check_unsaved_changes() {
+            return;
+        }
+
+        if self.active_pane_index < self.panes.len() {
+            self.panes.remove(self.active_pane_index);
+        }
+
+        self.active_pane_index = self
+            .active_pane_index
+            .min(self.panes.len().saturating_sub(1));
+    }
+
+    /// Closes every pane, refusing to do so while there are unsaved changes unless `force` is set
+    fn quit_all(&mut self, force: bool) {
+        if !force && !self.check_unsaved_changes() {
+            return;
+        }
+
+        self.panes.clear();
+    }
+
     fn should_close(&self) -> bool {
         self.panes.is_empty()
     }
 }
+
+/// Returns the message listing the flags of `document`, like `[New File]`, shown when it is
+/// opened or switched to
+fn get_flags_message(document: &Document) -> Option<Message> {
+    let flags = document.get_flags();
+
+    match (document.get_file_path(), flags.is_empty()) {
+        (Some(file_path), false) => Some(Message::Info(format!(
+            "\"{}\" {}",
+            file_path,
+            flags.join(" ")
+        ))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::TestBackend,
+        cli::FileArg,
+        testing::{
+            get_active_view, get_message, get_text, parse_keys, run, run_with_args, TempDir, HEIGHT,
+        },
+    };
+
+    #[tokio::test]
+    async fn typed_text_is_rendered() {
+        let editor = run("", "iHello<CR>world<Esc>").await;
+
+        assert_eq!(get_text(&editor), "Hello\nworld");
+        assert_eq!(
+            editor.backend.get_lines(),
+            [
+                "Hello",
+                "world",
+                "",
+                "",
+                "",
+                "",
+                "",
+                " NORMAL  [No Name] [+]  unix  100%  2:6"
+            ]
+        );
+        assert_eq!(editor.backend.get_cursor(), (5, 1));
+    }
+
+    #[tokio::test]
+    async fn undo_reverts_the_insert_session() {
+        let editor = run("", "iHello<Esc>u").await;
+
+        assert_eq!(get_text(&editor), "");
+        assert_eq!(editor.backend.get_lines()[0], "");
+    }
+
+    #[tokio::test]
+    async fn undoing_back_to_the_saved_state_clears_the_dirty_flag() {
+        let is_dirty =
+            |editor: &Chai<TestBackend>| get_active_view(editor).get_document().borrow().is_dirty();
+
+        assert!(!is_dirty(&run("", "iX<Esc>u").await));
+        assert!(is_dirty(&run("", "iX<Esc>uiY<Esc>").await));
+
+        let dir = TempDir::new("dirty");
+        let file_path = dir.create_file("dirty.txt", "text\n");
+        let args = || Args {
+            files: vec![FileArg {
+                file_path: file_path.clone(),
+                cursor: None,
+            }],
+            ..Default::default()
+        };
+
+        assert!(!is_dirty(
+            &run_with_args(args(), "iX<Esc>:w<CR>u<C-r>").await
+        ));
+        assert!(is_dirty(&run_with_args(args(), "iY<Esc>:w<CR>u").await));
+    }
+
+    #[tokio::test]
+    async fn quitting_refuses_to_drop_unsaved_changes() {
+        let editor = run("", "ix<Esc>:q<CR>").await;
+
+        assert!(!editor.should_close());
+        assert!(get_message(&editor)
+            .unwrap()
+            .starts_with("No write since last change"));
+
+        assert!(run("", "ix<Esc>:q!<CR>").await.should_close());
+        assert!(run("", "ix<Esc>:vs<CR>:qa!<CR>").await.should_close());
+        assert!(run("", ":q<CR>").await.should_close());
+    }
+
+    #[tokio::test]
+    async fn ctrl_c_asks_for_confirmation_with_unsaved_changes() {
+        let editor = run("", "ix<Esc><C-c>").await;
+
+        assert!(!editor.should_close());
+        assert_eq!(
+            get_message(&editor),
+            Some("Unsaved changes, press Ctrl-C again to quit without saving")
+        );
+
+        assert!(run("", "ix<Esc><C-c><C-c>").await.should_close());
+        assert!(!run("", "ix<Esc><C-c>l<C-c>").await.should_close());
+        assert!(run("", "<C-c>").await.should_close());
+    }
+
+    #[tokio::test]
+    async fn invalid_option_values_are_reported() {
+        for (keys, message) in [
+            (":set ts=0<CR>", "Invalid argument: 0"),
+            (":set ts=x<CR>", "Invalid argument: x"),
+            (":set ts+=2<CR>", "Invalid argument: ts+=2"),
+            (":set ff=mac<CR>", "Invalid file format: mac"),
+        ] {
+            assert_eq!(get_message(&run("", keys).await), Some(message), "{}", keys);
+        }
+    }
+
+    #[tokio::test]
+    async fn cursor_shape_follows_the_mode() {
+        let editor = run("", "i").await;
+
+        assert_eq!(editor.backend.get_cursor_shape(), Some(CursorShape::Bar));
+
+        let editor = run("", "i<Esc>").await;
+
+        assert_eq!(editor.backend.get_cursor_shape(), Some(CursorShape::Block));
+
+        let editor = run("", "r").await;
+
+        assert_eq!(
+            editor.backend.get_cursor_shape(),
+            Some(CursorShape::Underline)
+        );
+    }
+
+    #[tokio::test]
+    async fn file_is_opened_at_the_given_position() {
+        let dir = TempDir::new("position");
+        let args = Args {
+            files: vec![FileArg {
+                file_path: dir.create_file("position.txt", "one\ntwo\nthree\n"),
+                cursor: Some((2, 2).into()),
+            }],
+            ..Default::default()
+        };
+
+        let editor = run_with_args(args, "").await;
+
+        assert_eq!(editor.backend.get_lines()[..3], ["one", "two", "three"]);
+        assert_eq!(editor.backend.get_cursor(), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn vertical_split_shows_the_document_twice() {
+        let editor = run("ab", ":vs<CR>").await;
+
+        assert_eq!(
+            editor.backend.get_lines()[0],
+            format!("ab{}ab", " ".repeat(19))
+        );
+    }
+
+    #[tokio::test]
+    async fn read_only_documents_refuse_edits() {
+        let dir = TempDir::new("read-only");
+        let args = Args {
+            files: vec![FileArg {
+                file_path: dir.create_file("read-only.txt", "text\n"),
+                cursor: None,
+            }],
+            read_only: true,
+            ..Default::default()
+        };
+
+        let editor = run_with_args(args, "ix").await;
+
+        assert_eq!(get_text(&editor), "text");
+        assert_eq!(
+            editor.backend.get_lines().last().unwrap(),
+            "Cannot modify a read-only document"
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_files_open_empty_as_new_files() {
+        let dir = TempDir::new("new-file");
+        let file_path = dir.get_path().join("dir/new.txt");
+        let args = || Args {
+            files: vec![FileArg {
+                file_path: file_path.to_string_lossy().into_owned(),
+                cursor: None,
+            }],
+            ..Default::default()
+        };
+
+        let editor = run_with_args(args(), "").await;
+
+        assert_eq!(get_text(&editor), "");
+        assert!(get_message(&editor).unwrap().ends_with("[New File]"));
+
+        let editor = run_with_args(args(), "ix<Esc>:w<CR>").await;
+
+        assert!(get_message(&editor)
+            .unwrap()
+            .starts_with("Error writing file: Directory"));
+        assert!(!file_path.exists());
+
+        run_with_args(args(), "ix<Esc>:w ++p<CR>").await;
+
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "x\n");
+    }
+
+    #[tokio::test]
+    async fn switching_buffers_shows_their_flags() {
+        let dir = TempDir::new("buffer-flags");
+        let args = Args {
+            files: vec![
+                FileArg {
+                    file_path: dir.create_file("a.txt", "a\n"),
+                    cursor: None,
+                },
+                FileArg {
+                    file_path: dir.get_path().join("b.txt").to_string_lossy().into_owned(),
+                    cursor: None,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let editor = run_with_args(args, ":bn<CR>").await;
+
+        assert!(get_message(&editor)
+            .unwrap()
+            .ends_with("b.txt\" [New File]"));
+    }
+
+    #[tokio::test]
+    async fn write_commands_save_the_document() {
+        let dir = TempDir::new("write-commands");
+        let file_path = dir.create_file("a.txt", "text\n");
+        let other_path = dir.get_path().join("b.txt").to_string_lossy().into_owned();
+        let args = || Args {
+            files: vec![FileArg {
+                file_path: file_path.clone(),
+                cursor: None,
+            }],
+            ..Default::default()
+        };
+        let get_state = |editor: &Chai<TestBackend>| {
+            let document = get_active_view(editor).get_document().borrow();
+
+            (
+                document.get_file_path().map(str::to_owned),
+                document.is_dirty(),
+            )
+        };
+
+        // The status line only shows the modified flag, the path doesn't fit
+        let editor = run_with_args(args(), ":set stl=%m<CR>ix<Esc>").await;
+
+        assert_eq!(editor.backend.get_lines()[HEIGHT as usize - 1], "[+]");
+
+        let editor = run_with_args(args(), ":set stl=%m<CR>ix<Esc>:w<CR>l").await;
+
+        assert_eq!(editor.backend.get_lines()[HEIGHT as usize - 1], "");
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "xtext\n");
+
+        let editor = run_with_args(args(), &format!("iy<Esc>:w {}<CR>", other_path)).await;
+        assert_eq!(get_state(&editor), (Some(file_path.clone()), true));
+        assert_eq!(std::fs::read_to_string(&other_path).unwrap(), "yxtext\n");
+
+        let editor = run_with_args(args(), &format!("iz<Esc>:saveas {}<CR>", other_path)).await;
+        assert_eq!(get_state(&editor), (Some(other_path.clone()), false));
+        assert_eq!(std::fs::read_to_string(&other_path).unwrap(), "zxtext\n");
+
+        let editor = run_with_args(args(), "iw<Esc>:wq<CR>").await;
+
+        assert!(editor.should_close());
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "wxtext\n");
+    }
+
+    #[tokio::test]
+    async fn failed_saveas_keeps_the_document_path() {
+        let dir = TempDir::new("saveas");
+        let file_path = dir.create_file("saveas.txt", "text\n");
+        let args = Args {
+            files: vec![FileArg {
+                file_path: file_path.clone(),
+                cursor: None,
+            }],
+            ..Default::default()
+        };
+
+        let editor = run_with_args(
+            args,
+            "ix<Esc>:saveas /nonexistent-chai-dir/other.txt<CR>:w<CR>",
+        )
+        .await;
+
+        let document = get_active_view(&editor).get_document().borrow();
+
+        assert_eq!(document.get_file_path(), Some(file_path.as_str()));
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "xtext\n");
+    }
+
+    #[tokio::test]
+    async fn quitting_from_the_command_line_skips_the_ui() {
+        let args = Args {
+            commands: vec!["q".to_owned()],
+            ..Default::default()
+        };
+
+        let editor = run_with_args(args, "").await;
+
+        assert!(editor.should_close());
+        assert!(editor
+            .backend
+            .get_lines()
+            .iter()
+            .all(|line| line.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn resize_lays_the_views_out_again() -> anyhow::Result<()> {
+        let mut editor = run("ab", ":vs<CR>").await;
+
+        editor
+            .start(&mut futures_util::stream::iter([Ok(Event::Resize(11, 6))]))
+            .await?;
+
+        let view = editor.get_active_pane()?.get_active_view()?;
+
+        assert_eq!(
+            view.get_size(),
+            TermSize {
+                width: 5,
+                height: 5
+            }
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pending_keys_time_out() -> anyhow::Result<()> {
+        for (delay, expected) in [(500, "two"), (1500, "one two")] {
+            let mut editor = run("one two", "d").await;
+
+            // The `w` finishes `dw` unless it comes after the timeout, which drops the `d`
+            let keys = futures_util::stream::once(async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+
+                parse_keys("w").remove(0)
+            });
+
+            editor.start(&mut Box::pin(keys)).await?;
+
+            assert_eq!(get_text(&editor), expected);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use std::path::Path;
+
+use crate::core::Coords;
+
+pub const HELP: &str = "\
+Usage: chai [options] [file ...]
+
+Arguments:
+  file              A file to edit, optionally followed by :line or :line:column
+  -                 Read the text to edit from stdin
+
+Options:
+  +N                Start at line N of the first file
+  +                 Start at the last line of the first file
+  +/pattern         Start at the first match of pattern in the first file
+  -R                Read-only mode, the documents can't be modified
+  -c <command>      Run an ex command after loading the files, can be repeated
+  -h, --help        Print this help and exit
+  -V, --version     Print the version and exit
+  --                Treat every following argument as a file
+";
+
+/// Where the cursor starts in the first document, set with a `+` argument
+#[derive(Debug, PartialEq, Eq)]
+pub enum StartPosition {
+    Line(usize),
+    LastLine,
+    Pattern(String),
+}
+
+/// A file argument along with the position given with the `file:line:column` syntax
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileArg {
+    pub file_path: String,
+    pub cursor: Option<Coords>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Args {
+    pub files: Vec<FileArg>,
+    pub start: Option<StartPosition>,
+    pub read_only: bool,
+    pub commands: Vec<String>,
+}
+
+/// What the command line asks chai to do
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    Edit(Args),
+    Help,
+    Version,
+}
+
+/// Parses the command line arguments, without the program name
+pub fn parse(arguments: impl IntoIterator<Item = String>) -> anyhow::Result<Action> {
+    let mut args = Args::default();
+    let mut arguments = arguments.into_iter();
+    let mut only_files = false;
+
+    while let Some(argument) = arguments.next() {
+        if only_files || argument == "-" {
+            args.files.push(parse_file(argument));
+
+            continue;
+        }
+
+        match argument.as_str() {
+            "--" => only_files = true,
+            "-h" | "--help" => return Ok(Action::Help),
+            "-V" | "--version" => return Ok(Action::Version),
+            "-R" => args.read_only = true,
+            "-c" => {
+                let command = arguments
+                    .next()
+                    .ok_or(anyhow::anyhow!("Argument missing after: -c"))?;
+
+                args.commands.push(command);
+            }
+            "+" => args.start = Some(StartPosition::LastLine),
+            _ if argument.starts_with('+') => args.start = Some(parse_start(&argument[1..])?),
+            _ if argument.starts_with('-') => {
+                anyhow::bail!("Unknown option argument: {}", argument)
+            }
+            _ => args.files.push(parse_file(argument)),
+        };
+    }
+
+    Ok(Action::Edit(args))
+}
+
+fn parse_start(argument: &str) -> anyhow::Result<StartPosition> {
+    if let Some(pattern) = argument.strip_prefix('/') {
+        return Ok(StartPosition::Pattern(pattern.to_owned()));
+    }
+
+    let line = argument
+        .parse::<usize>()
+        .map_err(|_| anyhow::anyhow!("Invalid line number: +{}", argument))?;
+
+    Ok(StartPosition::Line(line))
+}
+
+/// Splits the `:line` or `:line:column` suffix printed by compilers from a file path. Both are
+/// 1-based, and a path that exists as given is never split.
+fn parse_file(argument: String) -> FileArg {
+    if Path::new(&argument).exists() {
+        return FileArg {
+            file_path: argument,
+            cursor: None,
+        };
+    }
+
+    let argument = argument.strip_suffix(':').unwrap_or(&argument).to_owned();
+
+    let Some((rest, last)) = argument
+        .rsplit_once(':')
+        .and_then(|(rest, last)| Some((rest, last.parse::<usize>().ok()?)))
+        .filter(|(rest, _)| !rest.is_empty())
+    else {
+        return FileArg {
+            file_path: argument,
+            cursor: None,
+        };
+    };
+
+    let (file_path, cursor) = match rest
+        .rsplit_once(':')
+        .and_then(|(path, line)| Some((path, line.parse::<usize>().ok()?)))
+        .filter(|(path, _)| !path.is_empty())
+    {
+        Some((path, line)) => (path, (last.saturating_sub(1), line.saturating_sub(1))),
+        None => (rest, (0, last.saturating_sub(1))),
+    };
+
+    FileArg {
+        file_path: file_path.to_owned(),
+        cursor: Some(cursor.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn parse_args(arguments: &[&str]) -> anyhow::Result<Action> {
+        parse(arguments.iter().map(|argument| argument.to_string()))
+    }
+
+    fn file(file_path: &str, cursor: Option<(usize, usize)>) -> FileArg {
+        FileArg {
+            file_path: file_path.to_owned(),
+            cursor: cursor.map(Coords::from),
+        }
+    }
+
+    #[test]
+    fn start_positions_are_parsed() {
+        for (argument, start) in [
+            ("12", StartPosition::Line(12)),
+            ("0", StartPosition::Line(0)),
+            ("/fn main", StartPosition::Pattern("fn main".to_owned())),
+            ("/", StartPosition::Pattern("".to_owned())),
+        ] {
+            assert_eq!(parse_start(argument).unwrap(), start, "+{}", argument);
+        }
+
+        for argument in ["", "x", "-1", "1x"] {
+            assert!(parse_start(argument).is_err(), "+{}", argument);
+        }
+    }
+
+    #[test]
+    fn positions_are_split_from_file_paths() {
+        for (argument, expected) in [
+            ("main.rs", file("main.rs", None)),
+            ("main.rs:12", file("main.rs", Some((0, 11)))),
+            ("main.rs:12:5", file("main.rs", Some((4, 11)))),
+            ("main.rs:12:5:", file("main.rs", Some((4, 11)))),
+            ("main.rs:0", file("main.rs", Some((0, 0)))),
+            ("dir:name/main.rs:3", file("dir:name/main.rs", Some((0, 2)))),
+            ("a:b", file("a:b", None)),
+            ("a:12b", file("a:12b", None)),
+            (":12", file(":12", None)),
+        ] {
+            assert_eq!(parse_file(argument.to_owned()), expected, "{}", argument);
+        }
+    }
+
+    #[test]
+    fn existing_paths_are_never_split() {
+        let dir = TempDir::new("cli");
+        let file_path = dir.create_file("main.rs:12", "");
+
+        assert_eq!(parse_file(file_path.clone()), file(&file_path, None));
+    }
+
+    #[test]
+    fn arguments_are_parsed() {
+        let args = |args: Args| Some(Action::Edit(args));
+
+        for (arguments, expected) in [
+            (&[][..], args(Args::default())),
+            (
+                &["a.txt", "+3", "b.txt:2"],
+                args(Args {
+                    files: vec![file("a.txt", None), file("b.txt", Some((0, 1)))],
+                    start: Some(StartPosition::Line(3)),
+                    ..Default::default()
+                }),
+            ),
+            (
+                &["+", "+/x"],
+                args(Args {
+                    start: Some(StartPosition::Pattern("x".to_owned())),
+                    ..Default::default()
+                }),
+            ),
+            (
+                &["-R", "-"],
+                args(Args {
+                    files: vec![file("-", None)],
+                    read_only: true,
+                    ..Default::default()
+                }),
+            ),
+            (
+                &["-c", "set ts=4", "-c", "q"],
+                args(Args {
+                    commands: vec!["set ts=4".to_owned(), "q".to_owned()],
+                    ..Default::default()
+                }),
+            ),
+            (
+                &["--", "-R", "+3", "--"],
+                args(Args {
+                    files: vec![file("-R", None), file("+3", None), file("--", None)],
+                    ..Default::default()
+                }),
+            ),
+            (&["a.txt", "--help"], Some(Action::Help)),
+            (&["-h", "-x"], Some(Action::Help)),
+            (&["-V"], Some(Action::Version)),
+            (&["--version"], Some(Action::Version)),
+            (&["-x"], None),
+            (&["--unknown"], None),
+            (&["-c"], None),
+            (&["+x"], None),
+        ] {
+            assert_eq!(parse_args(arguments).ok(), expected, "{:?}", arguments);
+        }
+    }
+}
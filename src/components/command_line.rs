@@ -0,0 +1,256 @@
+use crossterm::{
+    event::{KeyCode, KeyEvent, KeyModifiers},
+    style::{ContentStyle, Stylize},
+};
+
+use crate::{
+    chai::TermSize,
+    components::{Surface, TUIComponent},
+    core::TermScreenCoords,
+};
+
+pub enum Message {
+    Info(String),
+    Error(String),
+}
+
+/// The result of feeding a key to the command line
+pub enum CommandLineEvent {
+    None,
+    Submit(String),
+    Cancel,
+}
+
+/// The ex-style command line rendered in the bottom row of the window. It shows the command
+/// being typed while active and the last message otherwise.
+#[derive(Default)]
+pub struct CommandLine {
+    input: Vec<char>,
+    cursor: usize,
+    offset: usize,
+    history: Vec<String>,
+    history_index: Option<usize>,
+    message: Option<Message>,
+    active: bool,
+}
+
+impl TUIComponent for CommandLine {
+    fn render(&mut self, surface: &mut Surface) -> anyhow::Result<()> {
+        let window_size = surface.get_size();
+        let bottom = window_size.height.saturating_sub(1);
+
+        if self.active {
+            self.scroll(window_size);
+
+            let width = (window_size.width as usize).saturating_sub(1);
+            let visible = self
+                .input
+                .iter()
+                .skip(self.offset)
+                .take(width)
+                .collect::<String>();
+
+            surface.print(0, bottom, &format!(":{}", visible), ContentStyle::default());
+
+            return Ok(());
+        }
+
+        let width = window_size.width as usize;
+
+        let (message, is_error) = match &self.message {
+            Some(Message::Info(message)) => (message, false),
+            Some(Message::Error(message)) => (message, true),
+            None => return Ok(()),
+        };
+
+        // Messages with several lines, such as the buffer list, grow upwards over the views
+        let lines = message.lines().collect::<Vec<_>>();
+        let lines = &lines[lines.len().saturating_sub(window_size.height as usize)..];
+        let top = window_size.height.saturating_sub(lines.len() as u16);
+
+        let style = match is_error {
+            true => ContentStyle::new().red(),
+            false => ContentStyle::new(),
+        };
+
+        for (i, line) in lines.iter().enumerate() {
+            // Blank the whole row, so the views below don't show through
+            let line = format!("{:<width$}", line, width = width);
+
+            surface.print(0, top + i as u16, &line, style);
+        }
+
+        Ok(())
+    }
+}
+
+impl CommandLine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Starts reading a new command, discarding the last message
+    pub fn open(&mut self) {
+        self.active = true;
+        self.message = None;
+        self.input.clear();
+        self.cursor = 0;
+        self.offset = 0;
+        self.history_index = None;
+    }
+
+    /// Returns whether the command line has anything to show, a command or a message
+    pub fn is_visible(&self) -> bool {
+        self.active || self.message.is_some()
+    }
+
+    pub fn set_message(&mut self, message: Message) {
+        self.message = Some(message);
+    }
+
+    /// Returns the last message, which tests check in full since the screen cuts it
+    #[cfg(test)]
+    pub fn get_message(&self) -> Option<&Message> {
+        self.message.as_ref()
+    }
+
+    pub fn clear_message(&mut self) {
+        self.message = None;
+    }
+
+    pub fn get_cursor_term_pos(&self, window_size: TermSize) -> anyhow::Result<TermScreenCoords> {
+        Ok(TermScreenCoords {
+            x: (self.cursor.saturating_sub(self.offset) + 1).try_into()?,
+            y: window_size.height.saturating_sub(1),
+        })
+    }
+
+    pub fn handle_key(&mut self, event: &KeyEvent) -> CommandLineEvent {
+        match (event.modifiers, event.code) {
+            (KeyModifiers::NONE, KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char('c')) => {
+                self.active = false;
+
+                return CommandLineEvent::Cancel;
+            }
+            (KeyModifiers::NONE, KeyCode::Enter) => {
+                self.active = false;
+
+                let input = self.input.iter().collect::<String>();
+
+                if !input.trim().is_empty() && self.history.last() != Some(&input) {
+                    self.history.push(input.clone());
+                }
+
+                return CommandLineEvent::Submit(input);
+            }
+            (KeyModifiers::NONE, KeyCode::Backspace) if self.input.is_empty() => {
+                self.active = false;
+
+                return CommandLineEvent::Cancel;
+            }
+            (KeyModifiers::NONE, KeyCode::Backspace)
+            | (KeyModifiers::CONTROL, KeyCode::Char('h'))
+                if self.cursor > 0 =>
+            {
+                self.cursor -= 1;
+                self.input.remove(self.cursor);
+            }
+            (KeyModifiers::NONE, KeyCode::Delete) if self.cursor < self.input.len() => {
+                self.input.remove(self.cursor);
+            }
+            (KeyModifiers::NONE, KeyCode::Left) => {
+                self.cursor = self.cursor.saturating_sub(1);
+            }
+            (KeyModifiers::NONE, KeyCode::Right) => {
+                self.cursor = (self.cursor + 1).min(self.input.len());
+            }
+            (KeyModifiers::NONE, KeyCode::Home) | (KeyModifiers::CONTROL, KeyCode::Char('b')) => {
+                self.cursor = 0;
+            }
+            (KeyModifiers::NONE, KeyCode::End) | (KeyModifiers::CONTROL, KeyCode::Char('e')) => {
+                self.cursor = self.input.len();
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('u')) => {
+                self.input.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('w')) => {
+                let word_start = self.get_prev_word_start();
+
+                self.input.drain(word_start..self.cursor);
+                self.cursor = word_start;
+            }
+            (KeyModifiers::NONE, KeyCode::Up) => self.history_prev(),
+            (KeyModifiers::NONE, KeyCode::Down) => self.history_next(),
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+                self.input.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            _ => (),
+        };
+
+        CommandLineEvent::None
+    }
+
+    fn get_prev_word_start(&self) -> usize {
+        let before_cursor = &self.input[..self.cursor];
+
+        let word_end = before_cursor
+            .iter()
+            .rposition(|c| !c.is_whitespace())
+            .map(|i| i + 1)
+            .unwrap_or(0);
+
+        before_cursor[..word_end]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map(|i| i + 1)
+            .unwrap_or(0)
+    }
+
+    fn history_prev(&mut self) {
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+
+        self.set_input_from_history(Some(index));
+    }
+
+    fn history_next(&mut self) {
+        let Some(index) = self.history_index else {
+            return;
+        };
+
+        match index + 1 < self.history.len() {
+            true => self.set_input_from_history(Some(index + 1)),
+            false => self.set_input_from_history(None),
+        }
+    }
+
+    fn set_input_from_history(&mut self, index: Option<usize>) {
+        self.history_index = index;
+        self.input = index
+            .and_then(|index| self.history.get(index))
+            .map(|entry| entry.chars().collect())
+            .unwrap_or_default();
+        self.cursor = self.input.len();
+    }
+
+    fn scroll(&mut self, window_size: TermSize) {
+        let width = (window_size.width as usize).saturating_sub(1).max(1);
+
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        }
+
+        if self.cursor >= self.offset + width {
+            self.offset = self.cursor + 1 - width;
+        }
+    }
+}
@@ -1,13 +1,16 @@
-use std::io::Stdout;
-
-use crate::chai::TermSize;
-
+pub mod command_line;
 pub mod pane;
+pub mod status_line;
+pub mod surface;
 pub mod text_block;
 
+pub use command_line::CommandLine;
 pub use pane::Pane;
+pub use status_line::StatusLine;
+pub use surface::Surface;
 pub use text_block::TextBlock;
 
 pub trait TUIComponent {
-    fn render(&mut self, w: &mut Stdout, window_size: TermSize) -> anyhow::Result<()>;
+    /// Draws the component into `surface`, which covers the whole window
+    fn render(&mut self, surface: &mut Surface) -> anyhow::Result<()>;
 }
@@ -1,37 +1,42 @@
-use ropey::Rope;
+use std::rc::Rc;
 
 use crate::{
     chai::TermSize,
-    components::text_block::TextBlock,
-    core::{ExtendedLinkedList, TermScreenCoords},
+    components::{Surface, TUIComponent, TextBlock},
+    core::{document::DocumentHandle, TermScreenCoords},
 };
 
+pub enum SplitDirection {
+    /// Stacks the views on top of each other
+    Horizontal,
+    /// Places the views side by side, leaving a column between them
+    Vertical,
+}
+
 pub struct Pane {
     views: Vec<TextBlock>,
     active_view_index: usize,
 }
 
-impl Pane {
-    pub fn render(
-        &mut self,
-        w: &mut std::io::Stdout,
-        window_size: crate::chai::TermSize,
-    ) -> anyhow::Result<()> {
+impl TUIComponent for Pane {
+    fn render(&mut self, surface: &mut Surface) -> anyhow::Result<()> {
         for view in &mut self.views {
-            view.render(w, window_size)?;
+            view.render(surface)?;
         }
 
         Ok(())
     }
+}
 
+impl Pane {
     pub fn new(
-        content: ExtendedLinkedList<Rope>,
+        document: DocumentHandle,
         size: TermSize,
         position: TermScreenCoords,
         cursor: Option<(usize, usize)>,
     ) -> Self {
         Self {
-            views: vec![TextBlock::new(content, size, position, cursor)],
+            views: vec![TextBlock::new(document, size, position, cursor)],
             active_view_index: 0,
         }
     }
@@ -47,4 +52,184 @@ impl Pane {
             .get_mut(self.active_view_index)
             .ok_or(anyhow::anyhow!("No view found"))
     }
+
+    pub fn shows_document(&self, document: &DocumentHandle) -> bool {
+        self.views
+            .iter()
+            .any(|view| Rc::ptr_eq(view.get_document(), document))
+    }
+
+    pub fn get_views_mut(&mut self) -> &mut [TextBlock] {
+        &mut self.views
+    }
+
+    /// Splits the active view in two views of the same document. The new view is placed above
+    /// or to the left of the current one and becomes the active view.
+    pub fn split(&mut self, direction: SplitDirection) -> anyhow::Result<()> {
+        let view = self.get_active_view()?;
+        let position = view.get_position();
+        let size = view.get_size();
+
+        let (new_position, new_size, position, size) = match direction {
+            SplitDirection::Horizontal => {
+                if size.height < 2 {
+                    anyhow::bail!("Not enough room");
+                }
+
+                let new_height = size.height.div_ceil(2);
+
+                (
+                    position,
+                    TermSize {
+                        width: size.width,
+                        height: new_height,
+                    },
+                    TermScreenCoords {
+                        x: position.x,
+                        y: position.y + new_height,
+                    },
+                    TermSize {
+                        width: size.width,
+                        height: size.height - new_height,
+                    },
+                )
+            }
+            SplitDirection::Vertical => {
+                if size.width < 3 {
+                    anyhow::bail!("Not enough room");
+                }
+
+                let new_width = (size.width - 1).div_ceil(2);
+
+                (
+                    position,
+                    TermSize {
+                        width: new_width,
+                        height: size.height,
+                    },
+                    TermScreenCoords {
+                        x: position.x + new_width + 1,
+                        y: position.y,
+                    },
+                    TermSize {
+                        width: size.width - new_width - 1,
+                        height: size.height,
+                    },
+                )
+            }
+        };
+
+        let mut new_view = TextBlock::new(
+            view.get_document().clone(),
+            new_size,
+            new_position,
+            Some(view.get_cursor()),
+        );
+
+        // Like the other view options, the line numbers are kept in the new view
+        new_view.set_line_numbers(view.get_line_numbers());
+        new_view.set_wrap(view.get_wrap().clone());
+
+        self.get_current_view_mut()?.set_layout(position, size);
+        self.views.insert(self.active_view_index, new_view);
+
+        Ok(())
+    }
+
+    /// Scales the views laid out in `old_size` to fit `new_size`. The edges of the views are
+    /// scaled rather than their sizes, so views sharing an edge still do after rounding, and the
+    /// column between side by side views is kept one column wide.
+    pub fn resize(&mut self, old_size: TermSize, new_size: TermSize) {
+        let scale = |value: u16, old: u16, new: u16| {
+            let value = (value as u32 * new as u32 + old as u32 / 2) / (old as u32).max(1);
+
+            value.min(new as u32) as u16
+        };
+
+        for view in &mut self.views {
+            let position = view.get_position();
+            let size = view.get_size();
+
+            let x = match position.x {
+                0 => 0,
+                x => scale(x - 1, old_size.width, new_size.width) + 1,
+            };
+            let y = scale(position.y, old_size.height, new_size.height);
+            let right = scale(position.x + size.width, old_size.width, new_size.width);
+            let bottom = scale(position.y + size.height, old_size.height, new_size.height);
+
+            view.set_layout(
+                TermScreenCoords { x, y },
+                TermSize {
+                    width: right.saturating_sub(x).max(1),
+                    height: bottom.saturating_sub(y).max(1),
+                },
+            );
+        }
+    }
+
+    /// Closes the active view, handing its space to a neighbouring view. Returns `false` without
+    /// closing anything when it is the only view left.
+    pub fn close_active_view(&mut self) -> bool {
+        if self.views.len() <= 1 {
+            return false;
+        }
+
+        let closed = self.views.remove(self.active_view_index);
+        let (position, size) = (closed.get_position(), closed.get_size());
+
+        let neighbour = self.views.iter_mut().find_map(|view| {
+            let (view_position, view_size) = (view.get_position(), view.get_size());
+
+            let is_vertical_neighbour = view_position.x == position.x
+                && view_size.width == size.width
+                && (view_position.y + view_size.height == position.y
+                    || position.y + size.height == view_position.y);
+            let is_horizontal_neighbour = view_position.y == position.y
+                && view_size.height == size.height
+                && (view_position.x + view_size.width + 1 == position.x
+                    || position.x + size.width + 1 == view_position.x);
+
+            match (is_vertical_neighbour, is_horizontal_neighbour) {
+                (true, _) => Some((
+                    view,
+                    TermScreenCoords {
+                        x: position.x,
+                        y: position.y.min(view_position.y),
+                    },
+                    TermSize {
+                        width: size.width,
+                        height: size.height + view_size.height,
+                    },
+                )),
+                (_, true) => Some((
+                    view,
+                    TermScreenCoords {
+                        x: position.x.min(view_position.x),
+                        y: position.y,
+                    },
+                    TermSize {
+                        width: size.width + view_size.width + 1,
+                        height: size.height,
+                    },
+                )),
+                _ => None,
+            }
+        });
+
+        if let Some((view, position, size)) = neighbour {
+            view.set_layout(position, size);
+        }
+
+        self.active_view_index = self.active_view_index.min(self.views.len() - 1);
+
+        true
+    }
+
+    /// Moves the focus `steps` views forward, or backwards when negative, wrapping around
+    pub fn focus_view(&mut self, steps: isize) {
+        let len = self.views.len() as isize;
+
+        self.active_view_index = (self.active_view_index as isize + steps).rem_euclid(len) as usize;
+    }
 }
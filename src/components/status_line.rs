@@ -0,0 +1,132 @@
+use crossterm::style::{ContentStyle, Stylize};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    components::{text_block::Mode, Surface, TUIComponent, TextBlock},
+    core::{encoding, file_type},
+};
+
+pub const DEFAULT_FORMAT: &str = " %M  %f %m%r%=%y  %e  %p%%  %l:%c ";
+
+/// The status line shown in the bottom row of the window while the command line is empty. Its
+/// content is set with a format string of `%` items:
+///
+/// - `%M` the mode, `%f` the file path, `%t` the file name, `%m` `[+]` when modified, `%r` `[RO]`
+///   when read-only
+/// - `%l` the line, `%c` the column, `%L` the number of lines, `%p` the percentage through the
+///   file
+/// - `%y` the file type, `%e` the line ending, `%E` the encoding
+/// - `%=` aligns what follows to the right, `%%` is a literal `%`
+pub struct StatusLine {
+    format: String,
+    left: String,
+    right: String,
+}
+
+impl Default for StatusLine {
+    fn default() -> Self {
+        Self {
+            format: DEFAULT_FORMAT.to_owned(),
+            left: String::new(),
+            right: String::new(),
+        }
+    }
+}
+
+impl TUIComponent for StatusLine {
+    fn render(&mut self, surface: &mut Surface) -> anyhow::Result<()> {
+        let window_size = surface.get_size();
+        let y = window_size.height.saturating_sub(1);
+        let style = ContentStyle::new().reverse();
+
+        surface.print(0, y, &" ".repeat(window_size.width as usize), style);
+
+        // The right side gives way to the left side when both don't fit
+        let left_width = surface.print(0, y, &self.left, style);
+        let right_width = (self.right.width() as u16).min(window_size.width - left_width);
+
+        surface.print(window_size.width - right_width, y, &self.right, style);
+
+        Ok(())
+    }
+}
+
+impl StatusLine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_format(&self) -> &str {
+        &self.format
+    }
+
+    pub fn set_format(&mut self, format: String) {
+        self.format = format;
+    }
+
+    /// Formats the status of `view`, which is shown on the next render
+    pub fn update(&mut self, view: &TextBlock) {
+        let document = view.get_document().borrow();
+        let content = document.get_content();
+        let format = document.get_format();
+        let (x, y) = view.get_cursor();
+
+        let mut output = String::new();
+        let mut left = None;
+        let mut chars = self.format.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                output.push(c);
+
+                continue;
+            }
+
+            match chars.next() {
+                Some('M') => output.push_str(match view.get_mode() {
+                    Mode::Normal => "NORMAL",
+                    Mode::Insert => "INSERT",
+                    Mode::Replace => "REPLACE",
+                    Mode::Command => "COMMAND",
+                }),
+                Some('f') => output.push_str(document.get_file_path().unwrap_or("[No Name]")),
+                Some('t') => output.push_str(
+                    document
+                        .get_file_path()
+                        .and_then(|path| path.rsplit('/').next())
+                        .unwrap_or("[No Name]"),
+                ),
+                Some('m') if document.is_dirty() => output.push_str("[+]"),
+                Some('r') if document.is_read_only() => output.push_str("[RO]"),
+                Some('l') => output.push_str(&(y + 1).to_string()),
+                Some('c') => {
+                    let x = x.min(content.line_len(y).unwrap_or_default());
+
+                    output.push_str(&(x + 1).to_string());
+                }
+                Some('L') => output.push_str(&content.len_lines().to_string()),
+                Some('p') => {
+                    let percentage = (y + 1) * 100 / content.len_lines().max(1);
+
+                    output.push_str(&percentage.to_string());
+                }
+                Some('y') => output.push_str(
+                    document
+                        .get_file_path()
+                        .and_then(file_type::detect)
+                        .unwrap_or_default(),
+                ),
+                Some('e') => output.push_str(&format.line_ending.to_string()),
+                Some('E') => output.push_str(&encoding::get_name(format.encoding)),
+                Some('=') if left.is_none() => left = Some(std::mem::take(&mut output)),
+                Some('%') => output.push('%'),
+                _ => {}
+            };
+        }
+
+        (self.left, self.right) = match left {
+            Some(left) => (left, output),
+            None => (output, String::new()),
+        };
+    }
+}
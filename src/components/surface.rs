@@ -0,0 +1,136 @@
+use crossterm::style::ContentStyle;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{chai::TermSize, core::layout};
+
+/// A single character cell of the screen. Graphemes wider than a column are stored in their first
+/// cell, and the cells they cover have an empty symbol.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: String,
+    pub style: ContentStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: " ".to_owned(),
+            style: ContentStyle::default(),
+        }
+    }
+}
+
+/// A grid of cells the components render a frame into. Frames are compared with the previous
+/// one so only the cells that changed are sent to the terminal.
+#[derive(Clone)]
+pub struct Surface {
+    size: TermSize,
+    cells: Vec<Cell>,
+}
+
+impl Surface {
+    pub fn new(size: TermSize) -> Self {
+        Self {
+            size,
+            cells: vec![Cell::default(); size.width as usize * size.height as usize],
+        }
+    }
+
+    pub fn get_size(&self) -> TermSize {
+        self.size
+    }
+
+    /// Prints `text` starting at `(x, y)`, cutting it at the right edge. Returns the number of
+    /// columns printed.
+    pub fn print(&mut self, x: u16, y: u16, text: &str, style: ContentStyle) -> u16 {
+        let mut column = x;
+
+        for grapheme in text.graphemes(true) {
+            // Control chars would move the terminal cursor and break the grid, so they are shown
+            // like vim does
+            let (symbol, width) =
+                layout::get_symbol(grapheme, (column - x) as usize, layout::DEFAULT_TAB_STOP);
+
+            if !self.put(column, y, &symbol, width, style) {
+                break;
+            }
+
+            column += width as u16;
+        }
+
+        column - x
+    }
+
+    /// Puts a grapheme `width` columns wide at `(x, y)`. Returns false when it doesn't fit before
+    /// the right edge.
+    pub fn put(&mut self, x: u16, y: u16, symbol: &str, width: usize, style: ContentStyle) -> bool {
+        let Some(index) = self.get_index(x, y) else {
+            return false;
+        };
+
+        if x as usize + width.max(1) > self.size.width as usize {
+            return false;
+        }
+
+        let row_start = y as usize * self.size.width as usize;
+        let row_end = row_start + self.size.width as usize;
+        let end = index + width.max(1);
+
+        // The wide graphemes partly covered by this one are replaced with spaces
+        let first_covered = (row_start..=index)
+            .rev()
+            .find(|index| !self.cells[*index].symbol.is_empty())
+            .unwrap_or(index);
+        let last_covered = (end..row_end)
+            .take_while(|index| self.cells[*index].symbol.is_empty())
+            .last()
+            .map_or(end, |index| index + 1);
+
+        for cell in &mut self.cells[first_covered..last_covered] {
+            *cell = Cell {
+                symbol: " ".to_owned(),
+                style: cell.style,
+            };
+        }
+
+        self.cells[index] = Cell {
+            symbol: symbol.to_owned(),
+            style,
+        };
+
+        for cell in &mut self.cells[index + 1..end] {
+            *cell = Cell {
+                symbol: String::new(),
+                style,
+            };
+        }
+
+        true
+    }
+
+    /// Returns the position and content of every cell that differs from `previous`, or of every
+    /// cell when there is no previous frame of the same size to compare with
+    pub fn diff<'a>(
+        &'a self,
+        previous: Option<&'a Surface>,
+    ) -> impl Iterator<Item = (u16, u16, &'a Cell)> {
+        let previous = previous.filter(|previous| previous.size == self.size);
+        let width = self.size.width.max(1) as usize;
+
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(move |(index, cell)| {
+                previous.is_none_or(|previous| previous.cells[*index] != **cell)
+            })
+            .map(move |(index, cell)| ((index % width) as u16, (index / width) as u16, cell))
+    }
+
+    fn get_index(&self, x: u16, y: u16) -> Option<usize> {
+        if x >= self.size.width || y >= self.size.height {
+            return None;
+        }
+
+        Some(y as usize * self.size.width as usize + x as usize)
+    }
+}
@@ -1,105 +1,244 @@
-use std::{cmp::min, io::Stdout};
+use std::{
+    cell::Ref,
+    cmp::min,
+    ops::{Range, RangeInclusive},
+};
 
 use crossterm::{
-    cursor::{self},
     event::{Event, KeyCode, KeyEvent, KeyModifiers},
-    queue,
-    style::Print,
+    style::{ContentStyle, Stylize},
 };
-use once_cell::sync::Lazy;
-use ropey::Rope;
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
     chai::TermSize,
+    components::{Surface, TUIComponent},
     core::{
-        coords::{Coords, TermScreenCoords},
-        extended_linked_list::ExtendedLinkedList,
+        document::DocumentHandle,
+        layout::{Grapheme, LineLayout, WrapWidth},
+        motion::{self, CharSearch},
+        normal_command::{
+            Action, Motion, MotionKind, NormalCommand, Operator, Parse, Target, TextObject,
+        },
+        Buffer, Coords, TermScreenCoords,
     },
 };
 
-static WORD_REGEX: Lazy<regex::Regex> = Lazy::new(|| regex::Regex::new(r"\w+|[^\w\s]+").unwrap());
-
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
     Normal,
     Insert,
+    Replace,
     Command,
 }
 
+/// The shape of the terminal cursor, which tells the modes apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Block,
+    Bar,
+    Underline,
+}
+
+/// Which line numbers the gutter left of the text shows, set with `:set number` and
+/// `:set relativenumber`. With both set, the cursor line shows its number and the others their
+/// distance to it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LineNumbers {
+    pub number: bool,
+    pub relative_number: bool,
+}
+
+/// How lines longer than the view are shown, set with `:set wrap`, `:set linebreak` and
+/// `:set showbreak`. Without `wrap` the view scrolls horizontally instead.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Wrap {
+    pub enabled: bool,
+    /// Wraps lines at word boundaries rather than at the last column
+    pub line_break: bool,
+    /// Shown at the start of the rows a line continues on
+    pub show_break: String,
+}
+
+/// The text an operator applies to
+enum TextRange {
+    /// From the start up to the end, which is left out
+    Chars(Range<Coords>),
+    Lines(RangeInclusive<usize>),
+}
+
 pub struct TextBlock {
     position: TermScreenCoords,
-    content: ExtendedLinkedList<Rope>,
+    document: DocumentHandle,
     offset: Coords,
     size: TermSize,
     cursor: Coords,
     mode: Mode,
+    /// The keys of the normal mode command being typed, like `2d` before the motion
+    pending_keys: Vec<char>,
+    /// The last `f`, `F`, `t` or `T` search, which `;` and `,` repeat
+    last_char_search: Option<CharSearch>,
+    /// The display column `j` and `k` aim for, like vim's `curswant`, along with where they left
+    /// the cursor. It only holds while the cursor stays there.
+    desired_column: Option<(Coords, usize)>,
+    /// The chars overwritten in replace mode, `None` for the ones typed past the end of the line,
+    /// so backspace can bring them back
+    replaced: Vec<Option<char>>,
+    line_numbers: LineNumbers,
+    wrap: Wrap,
+    /// The rows of the top line that are scrolled out of view, for lines that wrap onto more
+    /// rows than the view has
+    skip_rows: usize,
 }
 
-impl TextBlock {
-    pub fn render(&mut self, w: &mut Stdout, window_size: TermSize) -> anyhow::Result<()> {
+impl TUIComponent for TextBlock {
+    fn render(&mut self, surface: &mut Surface) -> anyhow::Result<()> {
+        let window_size = surface.get_size();
+
+        self.clamp_cursor();
         self.scroll(window_size)?;
 
         let size = self.get_effective_size(window_size)?;
+        let gutter_width = self.get_gutter_width();
+
+        let lines_len = self.content().len_lines();
+        let mut skip_rows = self.skip_rows;
+        let mut y = self.position.y;
+        let bottom = self.position.y + size.height;
 
-        queue!(w, cursor::MoveTo(self.position.x, self.position.y))?;
-
-        let slices = self
-            .content
-            .iter()
-            .skip(self.offset.y)
-            .map(|l| {
-                l.get_slice(
-                    self.offset.x
-                        ..(self.offset.x + self.size.width as usize)
-                            .min(l.len_chars())
-                            .min(self.offset.x + size.width as usize),
-                )
-                .map(|s| s.as_str().unwrap_or(""))
-            })
-            .take(size.height as usize);
-
-        let len = slices.len();
-
-        for (i, slice) in slices.enumerate() {
-            queue!(w, Print(slice.unwrap_or("")))?;
-
-            if i < len.saturating_sub(1) {
-                queue!(w, Print("\n\r"))?;
-
-                if self.position.x > 0 {
-                    queue!(w, cursor::MoveRight(self.position.x))?;
+        for line_index in self.offset.y..lines_len {
+            let layout = self.get_layout(line_index);
+
+            for row in skip_rows..layout.len_rows() {
+                if y >= bottom {
+                    return Ok(());
+                }
+
+                let mut x = self.position.x + gutter_width;
+
+                // Only the first row of a line is numbered
+                if gutter_width > 0 && row == 0 {
+                    surface.print(
+                        self.position.x,
+                        y,
+                        &self.format_line_number(line_index, gutter_width),
+                        ContentStyle::new().dark_grey(),
+                    );
+                }
+
+                if row > 0 {
+                    x +=
+                        surface.print(x, y, self.get_show_break(), ContentStyle::new().dark_grey());
                 }
+
+                let width = (size.width + self.position.x + gutter_width).saturating_sub(x);
+                let graphemes = layout
+                    .get_graphemes()
+                    .iter()
+                    .filter(|grapheme| grapheme.row == row);
+
+                for grapheme in graphemes {
+                    self.render_grapheme(surface, x, y, width, grapheme);
+                }
+
+                y += 1;
             }
+
+            skip_rows = 0;
         }
 
         Ok(())
     }
+}
+
+impl TextBlock {
+    /// Draws `grapheme` on the row starting at `x`, which is `width` columns wide. The graphemes
+    /// only partly scrolled into view are drawn as spaces.
+    fn render_grapheme(
+        &self,
+        surface: &mut Surface,
+        x: u16,
+        y: u16,
+        width: u16,
+        grapheme: &Grapheme,
+    ) {
+        let visible = self.offset.x..self.offset.x + width as usize;
+        let start = grapheme.column.max(visible.start);
+        let end = (grapheme.column + grapheme.width).min(visible.end);
+
+        if start >= end {
+            return;
+        }
+
+        let x = x + (start - self.offset.x) as u16;
+
+        match start == grapheme.column && end == grapheme.column + grapheme.width {
+            true => surface.put(
+                x,
+                y,
+                &grapheme.symbol,
+                grapheme.width,
+                ContentStyle::default(),
+            ),
+            false => surface.put(
+                x,
+                y,
+                &" ".repeat(end - start),
+                end - start,
+                ContentStyle::default(),
+            ),
+        };
+    }
 
     pub fn new(
-        content: ExtendedLinkedList<Rope>,
+        document: DocumentHandle,
         size: TermSize,
         position: TermScreenCoords,
         cursor: Option<(usize, usize)>,
     ) -> TextBlock {
         TextBlock {
-            content,
+            document,
             size,
             offset: (0, 0).into(),
             position,
             cursor: cursor.unwrap_or((0, 0)).into(),
             mode: Mode::Normal,
+            pending_keys: vec![],
+            last_char_search: None,
+            desired_column: None,
+            replaced: vec![],
+            line_numbers: LineNumbers::default(),
+            wrap: Wrap::default(),
+            skip_rows: 0,
         }
     }
 
     pub fn get_cursor_term_pos(&self) -> anyhow::Result<TermScreenCoords> {
-        let x = self.cursor.x.saturating_sub(self.offset.x) + self.position.x as usize;
-        let y = self.cursor.y.saturating_sub(self.offset.y) + self.position.y as usize;
+        let (cursor_x, cursor_y) = self.get_cursor_pos()?;
+        let layout = self.get_layout(cursor_y);
+        let (row, mut column) = layout.get_position(cursor_x);
+
+        // Like in vim, the cursor is on the last column of a tab in normal mode
+        let on_tab = self
+            .content()
+            .line(cursor_y)
+            .and_then(|line| line.get_char(cursor_x))
+            == Some('\t');
+
+        if on_tab && self.mode == Mode::Normal {
+            column += layout.get_width(cursor_x).saturating_sub(1);
+        }
+
+        let mut x = self.position.x as usize + self.get_gutter_width() as usize;
+
+        if row > 0 {
+            x += self.get_show_break().width();
+        }
 
-        let line_len = self
-            .get_line_len(self.cursor.y)?
-            .saturating_sub(self.offset.x)
-            + self.position.x as usize;
+        x += column.saturating_sub(self.offset.x);
 
-        let x = x.min(line_len);
+        let rows_above = self.count_rows(self.offset.y..cursor_y) + row;
+        let y = self.position.y as usize + rows_above.saturating_sub(self.skip_rows);
 
         Ok(TermScreenCoords {
             x: x.try_into()?,
@@ -110,20 +249,33 @@ impl TextBlock {
     pub fn scroll(&mut self, window_size: TermSize) -> anyhow::Result<()> {
         let size = self.get_effective_size(window_size)?;
 
-        // When the cursor_y - offset_y is greater than window_height - 1, add one to the offset_y
+        if self.wrap.enabled {
+            self.offset.x = 0;
+
+            return self.scroll_rows(size.height as usize);
+        }
+
+        self.skip_rows = 0;
+
+        // When the cursor_y - offset_y is greater than window_height - 1, move the offset_y down
+        // just enough to show the cursor line at the bottom of the window
         if self.cursor.y.saturating_sub(self.offset.y) > size.height.saturating_sub(1) as usize {
-            self.offset.y += 1;
+            self.offset.y = self.cursor.y + 1 - (size.height as usize).max(1);
         }
         // When the cursor_y - offset_y is less than 0 (meaning, it hit the top of the window),
-        // subtract one from the offset_y
-        if (self.cursor.y + 1).saturating_sub(self.offset.y) == 0 {
-            self.offset.y = self.offset.y.saturating_sub(1);
+        // move the offset_y up to the cursor line
+        if self.cursor.y < self.offset.y {
+            self.offset.y = self.cursor.y;
         }
 
-        let current_line = self.get_line_at(self.cursor.y)?;
+        // The horizontal offset is in columns, which differ from chars for tabs and wide chars
+        let (cursor_x, cursor_y) = self.get_cursor_pos()?;
+        let layout = self.get_layout(cursor_y);
+        let (_, column) = layout.get_position(cursor_x);
 
-        let offset_cursor = self.cursor.x.saturating_sub(size.width as usize);
-        let offset_current_line = current_line.len_chars().saturating_sub(size.width as usize);
+        let offset_cursor =
+            (column + layout.get_width(cursor_x)).saturating_sub(size.width as usize);
+        let offset_current_line = layout.get_row_width(0).saturating_sub(size.width as usize);
 
         // When the cursor x is greater than the current line length, scroll the TextBlock back to
         // the minimum to show as most of the current line as possible
@@ -132,10 +284,94 @@ impl TextBlock {
         Ok(())
     }
 
+    /// Scrolls vertically when lines wrap, counting screen rows rather than lines
+    fn scroll_rows(&mut self, height: usize) -> anyhow::Result<()> {
+        let (cursor_x, cursor_y) = self.get_cursor_pos()?;
+        let (cursor_row, _) = self.get_layout(cursor_y).get_position(cursor_x);
+
+        // Above the view, the cursor row becomes the top row
+        if (cursor_y, cursor_row) < (self.offset.y, self.skip_rows) {
+            (self.offset.y, self.skip_rows) = (cursor_y, cursor_row);
+
+            return Ok(());
+        }
+
+        let rows_above = match cursor_y - self.offset.y < height {
+            true => self.count_rows(self.offset.y..cursor_y) + cursor_row - self.skip_rows,
+            false => height,
+        };
+
+        if rows_above < height {
+            return Ok(());
+        }
+
+        // Below the view, the top row moves down just enough to show the cursor row at the bottom
+        let (mut y, mut row) = (cursor_y, cursor_row);
+        let mut remaining = height.saturating_sub(1);
+
+        while remaining > row && y > 0 {
+            remaining -= row + 1;
+            y -= 1;
+            row = self.get_layout(y).len_rows() - 1;
+        }
+
+        (self.offset.y, self.skip_rows) = (y, row.saturating_sub(remaining));
+
+        Ok(())
+    }
+
+    /// Returns the columns and screen rows of line `y`, which is a single row holding the whole
+    /// line unless lines wrap
+    fn get_layout(&self, y: usize) -> LineLayout {
+        let content = self.content();
+        let line = content.line(y).unwrap_or("".into());
+
+        let wrap = self.wrap.enabled.then(|| {
+            let width = self.size.width.saturating_sub(self.get_gutter_width()) as usize;
+
+            WrapWidth {
+                width,
+                continuation_width: width.saturating_sub(self.get_show_break().width()),
+                line_break: self.wrap.line_break,
+            }
+        });
+
+        LineLayout::new(line, self.document.borrow().get_tab_stop(), wrap)
+    }
+
+    /// Returns the layout of line `y` as if it didn't wrap, so columns count from the start of
+    /// the line
+    fn get_line_layout(&self, y: usize) -> LineLayout {
+        let content = self.content();
+        let line = content.line(y).unwrap_or("".into());
+
+        LineLayout::new(line, self.document.borrow().get_tab_stop(), None)
+    }
+
+    /// Returns the number of screen rows taken by `lines`
+    fn count_rows(&self, lines: Range<usize>) -> usize {
+        match self.wrap.enabled {
+            true => lines.map(|y| self.get_layout(y).len_rows()).sum(),
+            false => lines.len(),
+        }
+    }
+
+    /// Returns the `showbreak` marker, unless it would fill the whole row
+    fn get_show_break(&self) -> &str {
+        let width = self.size.width.saturating_sub(self.get_gutter_width()) as usize;
+
+        match self.wrap.show_break.width() < width {
+            true => &self.wrap.show_break,
+            false => "",
+        }
+    }
+
+    /// Returns the size of the area the text is shown in, which is the part of the view inside
+    /// the window, without the gutter
     pub fn get_effective_size(&self, window_size: TermSize) -> anyhow::Result<TermSize> {
         let effective_width = (self.size.width as u32 + self.position.x as u32)
             .min(window_size.width as u32)
-            .saturating_sub(self.position.x as u32);
+            .saturating_sub(self.position.x as u32 + self.get_gutter_width() as u32);
         let effective_height = (self.size.height as u32 + self.position.y as u32)
             .min(window_size.height as u32)
             .saturating_sub(self.position.y as u32);
@@ -146,262 +382,701 @@ impl TextBlock {
         })
     }
 
-    pub fn update(&mut self, event: &Event) -> anyhow::Result<ExtendedLinkedList<Rope>> {
+    pub fn update(&mut self, event: &Event) -> anyhow::Result<()> {
+        // Another view on the same document may have removed the lines the cursor was on
+        self.clamp_cursor();
+
         match event {
             Event::Key(event) => self.handle_key(event)?,
             Event::Paste(_data) => (),
             _ => (),
         };
 
-        Ok(self.content.clone())
+        Ok(())
     }
 
-    fn get_line_at(&self, index: usize) -> anyhow::Result<&Rope> {
-        let line = self
-            .content
-            .get(index)
-            .ok_or(anyhow::anyhow!("No line at index {}", index))?;
+    pub fn get_document(&self) -> &DocumentHandle {
+        &self.document
+    }
 
-        Ok(line)
+    /// Shows another document in this view, starting where the cursor was when it was last left
+    pub fn set_document(&mut self, document: DocumentHandle) {
+        self.document.borrow_mut().set_last_cursor(self.cursor);
+
+        self.cursor = document.borrow().get_last_cursor();
+        self.document = document;
+        self.offset = (0, 0).into();
+        self.mode = Mode::Normal;
+        self.pending_keys.clear();
     }
 
-    fn get_line_at_mut(&mut self, index: usize) -> anyhow::Result<&mut Rope> {
-        let line = self
-            .content
-            .get_mut(index)
-            .ok_or(anyhow::anyhow!("No line at index {}", index))?;
+    pub fn get_cursor(&self) -> (usize, usize) {
+        (self.cursor.x, self.cursor.y)
+    }
 
-        Ok(line)
+    /// Moves the cursor to `cursor`, or as close as the document allows
+    pub fn set_cursor(&mut self, cursor: Coords) -> anyhow::Result<()> {
+        self.restore_cursor(cursor)
+    }
+
+    pub fn get_position(&self) -> TermScreenCoords {
+        self.position
+    }
+
+    pub fn get_size(&self) -> TermSize {
+        self.size
+    }
+
+    pub fn set_layout(&mut self, position: TermScreenCoords, size: TermSize) {
+        self.position = position;
+        self.size = size;
+    }
+
+    pub fn get_line_numbers(&self) -> LineNumbers {
+        self.line_numbers
+    }
+
+    pub fn set_line_numbers(&mut self, line_numbers: LineNumbers) {
+        self.line_numbers = line_numbers;
+    }
+
+    /// Returns the width of the line number gutter, which fits the number of the last line and a
+    /// space, with room for at least 3 digits like vim
+    fn get_gutter_width(&self) -> u16 {
+        if !self.line_numbers.number && !self.line_numbers.relative_number {
+            return 0;
+        }
+
+        let digits = self.content().len_lines().to_string().len().max(3);
+
+        (digits as u16 + 1).min(self.size.width)
+    }
+
+    fn format_line_number(&self, y: usize, gutter_width: u16) -> String {
+        let width = gutter_width.saturating_sub(1) as usize;
+        let distance = y.abs_diff(self.cursor.y);
+
+        match (self.line_numbers.number, self.line_numbers.relative_number) {
+            // The cursor line number is aligned to the left to stand out from the distances
+            (true, true) if distance == 0 => format!("{:<width$} ", y + 1),
+            (_, true) => format!("{:>width$} ", distance),
+            _ => format!("{:>width$} ", y + 1),
+        }
+    }
+
+    pub fn get_wrap(&self) -> &Wrap {
+        &self.wrap
+    }
+
+    pub fn set_wrap(&mut self, wrap: Wrap) {
+        self.wrap = wrap;
+    }
+
+    pub fn get_mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    /// Returns the cursor shape for the current mode. Like vim, the cursor is an underline while
+    /// a key is pending, such as the char to replace with after `r`.
+    pub fn get_cursor_shape(&self) -> CursorShape {
+        // A count alone doesn't change it
+        let pending = self.pending_keys.iter().any(|key| !key.is_ascii_digit());
+
+        match (self.mode, pending) {
+            (Mode::Normal, true) | (Mode::Replace, _) => CursorShape::Underline,
+            (Mode::Normal, false) => CursorShape::Block,
+            (Mode::Insert | Mode::Command, _) => CursorShape::Bar,
+        }
+    }
+
+    fn content(&self) -> Ref<'_, Buffer> {
+        Ref::map(self.document.borrow(), |document| document.get_content())
     }
 
     fn get_line_len(&self, index: usize) -> anyhow::Result<usize> {
-        let line = self.get_line_at(index)?;
+        self.content()
+            .line_len(index)
+            .ok_or(anyhow::anyhow!("No line at index {}", index))
+    }
 
-        Ok(line.len_chars())
+    /// Moves the cursor back inside the document after it was edited from somewhere else
+    fn clamp_cursor(&mut self) {
+        self.goto_line(self.cursor.y);
     }
 
     fn goto_line(&mut self, line_number: usize) {
-        let lines_len = self.content.len();
+        let lines_len = self.content().len_lines();
 
         self.cursor.y = line_number.min(lines_len.saturating_sub(1));
     }
 
-    fn get_prev_word_start(&self) -> Option<usize> {
-        let line = self
-            .get_line_at(self.cursor.y)
-            .ok()?
-            .get_slice(0..self.cursor.x)?
-            .as_str()?;
+    fn handle_key(&mut self, event: &KeyEvent) -> anyhow::Result<()> {
+        match (self.mode, event.modifiers, event.code) {
+            (Mode::Normal, KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+                self.pending_keys.push(c);
+
+                match NormalCommand::parse(&self.pending_keys) {
+                    Parse::Pending => return Ok(()),
+                    Parse::Invalid => self.pending_keys.clear(),
+                    Parse::Complete(command) => {
+                        self.pending_keys.clear();
+                        self.execute(command)?;
+                    }
+                }
+            }
+            _ => {
+                // Other keys, like Esc, drop the keys typed so far. A count alone still applies,
+                // like with `3<Down>`.
+                let count = NormalCommand::parse_count(&self.pending_keys);
+
+                self.pending_keys.clear();
+                self.handle_key_inner(event, count)?;
+            }
+        }
+
+        // Insert and replace mode sessions are undone as a whole, everything else one key at a
+        // time
+        if !matches!(self.mode, Mode::Insert | Mode::Replace) {
+            self.document.borrow_mut().commit();
+        }
 
-        let capture = WORD_REGEX.captures_iter(line).last()?.get(0)?;
+        Ok(())
+    }
 
-        Some(capture.start())
+    /// Returns whether part of a normal mode command was typed, which waits for more keys
+    pub fn has_pending_keys(&self) -> bool {
+        !self.pending_keys.is_empty()
     }
 
-    fn get_next_word_end(&self) -> Option<usize> {
-        let line = self
-            .get_line_at(self.cursor.y)
-            .ok()?
-            .get_slice(0..)?
-            .as_str()?;
+    /// Drops the part of a normal mode command typed so far, when the next key took too long
+    pub fn cancel_pending_keys(&mut self) {
+        self.pending_keys.clear();
+    }
 
-        let captures = WORD_REGEX.captures_iter(line);
-        let mut captures = captures.skip_while(|c| match c.get(0) {
-            Some(capture) => capture.end() <= self.cursor.x,
-            None => false,
-        });
+    fn execute(&mut self, command: NormalCommand) -> anyhow::Result<()> {
+        if let Action::Move(Motion::FindChar(search))
+        | Action::Operate(_, Target::Motion(Motion::FindChar(search))) = command.action
+        {
+            self.last_char_search = Some(search);
+        }
 
-        let capture = captures.next()?.get(0)?;
+        match command.action {
+            Action::Move(motion) => self.move_cursor(motion, command.count)?,
+            Action::Operate(operator, target) => self.operate(operator, target, command.count)?,
+            Action::Insert => self.mode = Mode::Insert,
+            Action::Replace => {
+                self.mode = Mode::Replace;
+                self.replaced.clear();
+            }
+            Action::ReplaceChar(c) => self.replace_char(c)?,
+            Action::Undo | Action::Redo => {
+                for _ in 0..command.get_count() {
+                    let cursor = match command.action {
+                        Action::Undo => self.document.borrow_mut().undo()?,
+                        _ => self.document.borrow_mut().redo()?,
+                    };
+
+                    match cursor {
+                        Some(cursor) => self.restore_cursor(cursor)?,
+                        None => break,
+                    }
+                }
+            }
+            Action::Travel { forward } => {
+                let steps = command.get_count().min(isize::MAX as usize) as isize;
+                let steps = match forward {
+                    true => steps,
+                    false => -steps,
+                };
 
-        let word_end = capture.end() - 1;
+                let cursor = self.document.borrow_mut().travel(steps)?;
 
-        if word_end == self.cursor.x {
-            return Some(captures.next()?.get(0)?.end() - 1);
+                if let Some(cursor) = cursor {
+                    self.restore_cursor(cursor)?;
+                }
+            }
+            Action::CommandLine => self.mode = Mode::Command,
         }
 
-        Some(word_end)
+        Ok(())
     }
 
-    fn get_first_word_end_at_line(&self, line_number: usize) -> Option<usize> {
-        let line = self
-            .get_line_at(line_number)
-            .ok()?
-            .get_slice(0..)?
-            .as_str()?;
+    /// Moves the cursor with `motion`, keeping track of the column `j` and `k` aim for
+    fn move_cursor(&mut self, motion: Motion, count: Option<usize>) -> anyhow::Result<()> {
+        let column = match motion {
+            Motion::Up | Motion::Down => Some(self.get_desired_column()?),
+            // Like vim, `j` and `k` stick to the end of the lines after `$`
+            Motion::LineEnd => Some(usize::MAX),
+            _ => None,
+        };
 
-        let mut captures = WORD_REGEX.captures_iter(line);
-        let capture = captures.next()?.get(0)?;
+        if let Some(cursor) = self.get_motion_target(motion, count)? {
+            self.cursor = cursor;
+        }
 
-        let word_end = capture.end() - 1;
+        self.desired_column = column.map(|column| (self.cursor, column));
 
-        Some(word_end)
+        Ok(())
     }
 
-    fn get_next_word_start(&self) -> Option<usize> {
-        let line = self
-            .get_line_at(self.cursor.y)
-            .ok()?
-            .get_slice(0..)?
-            .as_str()?;
+    /// Returns the display column `j` and `k` aim for, which is the one of the cursor unless it
+    /// was left on a shorter line by the last of them
+    fn get_desired_column(&self) -> anyhow::Result<usize> {
+        if let Some((cursor, column)) = self.desired_column {
+            if cursor == self.cursor {
+                return Ok(column);
+            }
+        }
 
-        let captures = WORD_REGEX.captures_iter(line);
-        let mut captures = captures.skip_while(|c| match c.get(0) {
-            Some(capture) => capture.end() <= self.cursor.x,
-            None => false,
-        });
+        let (x, y) = self.get_cursor_pos()?;
 
-        captures.next();
+        Ok(self.get_line_layout(y).get_position(x).1)
+    }
 
-        let capture = captures.next()?.get(0)?;
-        let word_end = capture.start();
+    /// Returns the position of the grapheme shown at display `column` of line `y`, or of the
+    /// last one when the line isn't as wide. Insert and replace mode can also go past it.
+    fn get_column_x(&self, y: usize, column: usize) -> anyhow::Result<usize> {
+        let layout = self.get_line_layout(y);
 
-        if word_end == self.cursor.x {
-            return Some(captures.next()?.get(0)?.start());
+        match self.mode {
+            Mode::Insert | Mode::Replace if column >= layout.get_row_width(0) => {
+                self.get_line_len(y)
+            }
+            _ => Ok(layout.get_x(0, column)),
         }
-
-        Some(word_end)
     }
 
-    fn get_first_word_start_at_line(&self, line_number: usize) -> Option<usize> {
-        let line = self
-            .get_line_at(line_number)
-            .ok()?
-            .get_slice(0..)?
-            .as_str()?;
+    /// Returns where `motion` moves the cursor when repeated `count` times, or `None` when it
+    /// can't move, like `f` without the char in the rest of the line
+    fn get_motion_target(
+        &self,
+        motion: Motion,
+        count: Option<usize>,
+    ) -> anyhow::Result<Option<Coords>> {
+        let content = self.content();
+        let times = count.unwrap_or(1);
+        let last_line = content.len_lines() - 1;
+
+        let target = match motion {
+            Motion::Up | Motion::Down => {
+                let y = match motion {
+                    Motion::Up => self.cursor.y.saturating_sub(times),
+                    _ => self.cursor.y.saturating_add(times).min(last_line),
+                };
 
-        let mut captures = WORD_REGEX.captures_iter(line);
+                Coords {
+                    x: self.get_column_x(y, self.get_desired_column()?)?,
+                    y,
+                }
+            }
+            Motion::LineEnd => motion::line_end(
+                &content,
+                Coords {
+                    x: 0,
+                    y: self.cursor.y.saturating_add(times - 1).min(last_line),
+                },
+            )?,
+            Motion::Line => {
+                motion::goto_line(&content, count.map_or(last_line, |count| count - 1))?
+            }
+            Motion::FirstLine => motion::goto_line(&content, count.map_or(0, |count| count - 1))?,
+            Motion::MatchingBracket => match count {
+                // Like vim, `50%` goes to the middle of the buffer
+                Some(percent) => motion::goto_line(
+                    &content,
+                    (percent.min(100) * content.len_lines()).div_ceil(100) - 1,
+                )?,
+                None => return motion::matching_bracket(&content, self.cursor),
+            },
+            Motion::ScreenTop | Motion::ScreenMiddle | Motion::ScreenBottom => {
+                motion::first_non_blank(&content, self.get_screen_line(motion, times))?
+            }
+            _ => {
+                // Repeated searches skip the char right next to the cursor with `t` and `T`
+                let (motion, repeated) = match motion {
+                    Motion::RepeatCharSearch { reversed } => match self.last_char_search {
+                        Some(search) if reversed => (Motion::FindChar(search.reversed()), true),
+                        Some(search) => (Motion::FindChar(search), true),
+                        None => return Ok(None),
+                    },
+                    motion => (motion, false),
+                };
+
+                let mut target = self.cursor;
+
+                for index in 0..times {
+                    match self.get_motion_step(&content, motion, target, repeated || index > 0)? {
+                        Some(next) if next != target => target = next,
+                        Some(_) => break,
+                        None => return Ok(None),
+                    }
+                }
+
+                target
+            }
+        };
+
+        Ok(Some(target))
+    }
 
-        match captures.next() {
-            Some(capture) => match capture.get(0) {
-                Some(r#match) => Some(r#match.start()),
-                None => Some(0),
+    /// Returns where a single `motion` moves the cursor from `from`, for the motions that repeat
+    /// with a count
+    fn get_motion_step(
+        &self,
+        content: &Buffer,
+        motion: Motion,
+        from: Coords,
+        repeated: bool,
+    ) -> anyhow::Result<Option<Coords>> {
+        let x = from.x.min(self.get_line_len(from.y)?);
+
+        let target = match motion {
+            Motion::Left => Coords {
+                x: self.get_layout(from.y).get_prev_x(x),
+                y: from.y,
             },
-            None => Some(0),
-        }
+            // A cursor kept past the end of a shorter line stays there
+            Motion::Right if from.x < self.get_line_len(from.y)? => Coords {
+                x: self.get_layout(from.y).get_next_x(x),
+                y: from.y,
+            },
+            Motion::RowDown => self.get_row_target(from, true)?,
+            Motion::RowUp => self.get_row_target(from, false)?,
+            Motion::WordStart { big_word } => motion::next_word_start(content, from, big_word)?,
+            Motion::PrevWordStart { big_word } => motion::prev_word_start(content, from, big_word)?,
+            Motion::WordEnd { big_word } => motion::next_word_end(content, from, big_word)?,
+            Motion::LineStart => motion::line_start(from),
+            Motion::FirstNonBlank => motion::first_non_blank(content, from.y)?,
+            Motion::NextParagraph => motion::next_paragraph(content, from)?,
+            Motion::PrevParagraph => motion::prev_paragraph(content, from)?,
+            Motion::NextSentence => motion::next_sentence(content, from)?,
+            Motion::PrevSentence => motion::prev_sentence(content, from)?,
+            Motion::FindChar(search) => return motion::find_char(content, from, search, repeated),
+            _ => from,
+        };
+
+        Ok(Some(target))
     }
 
-    fn get_last_word_start_at_line(&self, line_number: usize) -> Option<usize> {
-        let line = self
-            .get_line_at(line_number)
-            .ok()?
-            .get_slice(0..)?
-            .as_str()?;
+    /// Returns the kind of `motion`, which for `;` and `,` is the one of the search they repeat
+    fn get_motion_kind(&self, motion: Motion) -> MotionKind {
+        match (motion, self.last_char_search) {
+            (Motion::RepeatCharSearch { reversed }, Some(search)) if reversed => {
+                Motion::FindChar(search.reversed()).get_kind()
+            }
+            (Motion::RepeatCharSearch { .. }, Some(search)) => Motion::FindChar(search).get_kind(),
+            _ => motion.get_kind(),
+        }
+    }
 
-        let last = WORD_REGEX.captures_iter(line).last();
+    /// Returns the line `H`, `M` or `L` moves to, which is the first, middle or last line that
+    /// starts on screen. With a count, `H` and `L` move that many lines from the top or bottom.
+    fn get_screen_line(&self, motion: Motion, times: usize) -> usize {
+        let height = self.size.height as usize;
+        let len_lines = self.content().len_lines();
+        let first = self.offset.y.min(len_lines - 1);
+        let mut last = first;
+        let mut rows = self.count_rows(first..first + 1) - self.skip_rows;
+
+        while last + 1 < len_lines && rows < height {
+            last += 1;
+            rows += self.count_rows(last..last + 1);
+        }
 
-        match last {
-            Some(capture) => match capture.get(0) {
-                Some(r#match) => Some(r#match.start()),
-                None => Some(0),
-            },
-            None => Some(0),
+        match motion {
+            Motion::ScreenTop => first.saturating_add(times - 1).min(last),
+            Motion::ScreenMiddle => (first + last) / 2,
+            _ => last.saturating_sub(times - 1).max(first),
         }
     }
 
-    fn handle_key(&mut self, event: &KeyEvent) -> anyhow::Result<()> {
-        match (event.modifiers, event.code, &self.mode) {
-            // Global movement
-            (KeyModifiers::NONE, KeyCode::Left, _)
-            | (KeyModifiers::NONE, KeyCode::Char('h'), Mode::Normal) => {
-                let line = self.get_line_at(self.cursor.y)?;
-                self.cursor.x = self
-                    .cursor
-                    .x
-                    .saturating_sub(1)
-                    .min(line.len_chars().saturating_sub(1));
+    /// Applies `operator` to the text `target` covers from the cursor. Changes leave the cursor
+    /// in insert mode, unless the motion failed.
+    fn operate(
+        &mut self,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+    ) -> anyhow::Result<()> {
+        let (_, y) = self.get_cursor_pos()?;
+
+        let range = match target {
+            Target::Line => {
+                let last_line = self.content().len_lines() - 1;
+
+                Some(TextRange::Lines(
+                    y..=y.saturating_add(count.unwrap_or(1) - 1).min(last_line),
+                ))
             }
-            (KeyModifiers::NONE, KeyCode::Right, _)
-            | (KeyModifiers::NONE, KeyCode::Char('l'), Mode::Normal) => {
-                let line = self.get_line_at(self.cursor.y)?;
+            Target::Motion(motion) => self.get_motion_range(operator, motion, count)?,
+            Target::TextObject(object) => self.get_object_range(operator, object)?,
+        };
 
-                let prev_cursor_x = self.cursor.x;
+        let Some(range) = range else {
+            return Ok(());
+        };
 
-                self.cursor.x = (self.cursor.x + 1).min(line.len_chars());
-                self.cursor.x = self.cursor.x.max(prev_cursor_x);
+        match (operator, range) {
+            (_, TextRange::Chars(range)) => {
+                self.remove_text(range.clone())?;
+                self.cursor = range.start;
             }
-            (KeyModifiers::NONE, KeyCode::Up, _)
-            | (KeyModifiers::NONE, KeyCode::Char('k'), Mode::Normal) => {
-                self.goto_line(self.cursor.y.saturating_sub(1));
+            (Operator::Delete, TextRange::Lines(lines)) => {
+                let (start, end) = (*lines.start(), *lines.end());
+                let last_line = self.content().len_lines() - 1;
+
+                // The last lines take the line break before them along, as there is none after
+                let range = match (end < last_line, start) {
+                    (true, _) => Coords { x: 0, y: start }..Coords { x: 0, y: end + 1 },
+                    (false, 0) => Coords { x: 0, y: 0 }..self.get_line_end(last_line)?,
+                    (false, _) => self.get_line_end(start - 1)?..self.get_line_end(last_line)?,
+                };
+
+                self.remove_text(range)?;
+
+                let y = start.min(self.content().len_lines() - 1);
+
+                let cursor = motion::first_non_blank(&self.content(), y)?;
+
+                self.cursor = cursor;
             }
-            (KeyModifiers::NONE, KeyCode::Down, _)
-            | (KeyModifiers::NONE, KeyCode::Char('j'), Mode::Normal) => {
-                self.goto_line(self.cursor.y + 1);
+            (Operator::Change, TextRange::Lines(lines)) => {
+                let start = Coords {
+                    x: 0,
+                    y: *lines.start(),
+                };
+
+                self.remove_text(start..self.get_line_end(*lines.end())?)?;
+                self.cursor = start;
             }
-            (_, KeyCode::Char(':'), Mode::Normal) => {
-                self.mode = Mode::Command;
+        }
+
+        if operator == Operator::Change {
+            self.mode = Mode::Insert;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the text `motion` moves over from the cursor, following vim's exceptions for
+    /// operators
+    fn get_motion_range(
+        &self,
+        operator: Operator,
+        motion: Motion,
+        count: Option<usize>,
+    ) -> anyhow::Result<Option<TextRange>> {
+        let (x, y) = self.get_cursor_pos()?;
+        let from = Coords { x, y };
+        let content = self.content();
+
+        // `cw` on a word changes up to its end, leaving the blanks after it alone
+        if let (Operator::Change, Motion::WordStart { big_word }) = (operator, motion) {
+            let on_word = content
+                .line(y)
+                .and_then(|line| line.get_char(x))
+                .is_some_and(|c| !c.is_whitespace());
+
+            if on_word {
+                let word = motion::word_object(&content, from, big_word, false)?;
+                let mut end = Coords {
+                    x: word.end.x - 1,
+                    y,
+                };
+
+                for _ in 1..count.unwrap_or(1) {
+                    end = motion::next_word_end(&content, end, big_word)?;
+                }
+
+                return Ok(Some(TextRange::Chars(from..self.get_grapheme_end(end)?)));
             }
+        }
 
-            // Normal mode
-            (KeyModifiers::NONE, KeyCode::Char('i'), Mode::Normal) => {
-                self.mode = Mode::Insert;
+        let Some(target) = self.get_motion_target(motion, count)? else {
+            return Ok(None);
+        };
+
+        let target = Coords {
+            x: target.x.min(self.get_line_len(target.y)?),
+            y: target.y,
+        };
+
+        let (start, end) = match (target.y, target.x) < (from.y, from.x) {
+            true => (target, from),
+            false => (from, target),
+        };
+
+        let range = match self.get_motion_kind(motion) {
+            MotionKind::Linewise => TextRange::Lines(start.y..=end.y),
+            MotionKind::Inclusive => TextRange::Chars(start..self.get_grapheme_end(end)?),
+            // A word motion ending on a later line stops at the end of the last word moved over
+            MotionKind::Exclusive
+                if end.y > start.y && matches!(motion, Motion::WordStart { .. }) =>
+            {
+                TextRange::Chars(start..self.get_line_end(end.y - 1)?)
+            }
+            // An exclusive motion ending at the start of a later line doesn't take that line's
+            // break along, and covers whole lines when it started at the start of its line too
+            MotionKind::Exclusive if end.y > start.y && end.x == 0 => {
+                match start.x <= motion::first_non_blank(&content, start.y)?.x {
+                    true => TextRange::Lines(start.y..=end.y - 1),
+                    false => TextRange::Chars(start..self.get_line_end(end.y - 1)?),
+                }
             }
-            (KeyModifiers::NONE, KeyCode::Char('b'), Mode::Normal) => {
-                let prev_start = self.get_prev_word_start();
+            MotionKind::Exclusive => TextRange::Chars(start..end),
+        };
 
-                match prev_start {
-                    Some(prev_start) => {
-                        self.cursor.x = prev_start;
-                    }
-                    None => {
-                        if self.cursor.y == 0 {
-                            return Ok(());
-                        }
-
-                        let Some(prev_line_start) =
-                            self.get_last_word_start_at_line(self.cursor.y - 1)
-                        else {
-                            return Ok(());
-                        };
-
-                        self.cursor.x = prev_line_start;
-                        self.cursor.y -= 1;
-                    }
-                };
+        Ok(Some(range))
+    }
+
+    fn get_object_range(
+        &self,
+        operator: Operator,
+        object: TextObject,
+    ) -> anyhow::Result<Option<TextRange>> {
+        let (x, y) = self.get_cursor_pos()?;
+        let content = self.content();
+
+        let range = match object {
+            TextObject::Word { big_word, around } => {
+                motion::word_object(&content, Coords { x, y }, big_word, around)?
             }
-            (KeyModifiers::NONE, KeyCode::Char('e'), Mode::Normal) => {
-                match self.get_next_word_end() {
-                    Some(word_end) => {
-                        self.cursor.x = word_end;
-                    }
-                    None => {
-                        let Some(next_word_end) =
-                            self.get_first_word_end_at_line(self.cursor.y + 1)
-                        else {
-                            return Ok(());
-                        };
-
-                        self.cursor.x = next_word_end;
-                        self.cursor.y += 1;
-                    }
+            TextObject::Block {
+                open,
+                close,
+                around,
+            } => {
+                let Some(mut range) =
+                    motion::block_object(&content, Coords { x, y }, open, close, around)?
+                else {
+                    return Ok(None);
+                };
+
+                // Changing the inside of a block over several lines leaves a line to type on
+                if operator == Operator::Change && range.end.x == 0 && range.end.y > range.start.y {
+                    range.end = self.get_line_end(range.end.y - 1)?;
                 }
+
+                range
             }
-            (KeyModifiers::NONE, KeyCode::Char('w'), Mode::Normal) => {
-                match self.get_next_word_start() {
-                    Some(word_start) => {
-                        self.cursor.x = word_start;
-                    }
-                    None => match self.get_first_word_start_at_line(self.cursor.y + 1) {
-                        Some(next_word_start) => {
-                            self.get_first_word_start_at_line(self.cursor.y + 1);
-                            self.cursor.x = next_word_start;
-                            self.cursor.y += 1;
-                        }
-                        None => {
-                            let Some(current_word_end) = self.get_next_word_end() else {
-                                return Ok(());
-                            };
-
-                            self.cursor.x = current_word_end;
-                        }
-                    },
+        };
+
+        Ok(Some(TextRange::Chars(range)))
+    }
+
+    /// Returns the position right after the grapheme at `at`
+    fn get_grapheme_end(&self, at: Coords) -> anyhow::Result<Coords> {
+        let x = match at.x < self.get_line_len(at.y)? {
+            true => self.get_layout(at.y).get_next_x(at.x),
+            false => at.x,
+        };
+
+        Ok(Coords { x, y: at.y })
+    }
+
+    /// Returns the position right after the last char of line `y`
+    fn get_line_end(&self, y: usize) -> anyhow::Result<Coords> {
+        Ok(Coords {
+            x: self.get_line_len(y)?,
+            y,
+        })
+    }
+
+    /// Removes the text from the start of `range` up to its end, which is left
+    fn remove_text(&mut self, range: Range<Coords>) -> anyhow::Result<()> {
+        let len = {
+            let content = self.content();
+
+            content
+                .char_index(range.end)?
+                .saturating_sub(content.char_index(range.start)?)
+        };
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        let (x, y) = self.get_cursor_pos()?;
+
+        self.document
+            .borrow_mut()
+            .remove(range.start, len, Coords { x, y })?;
+
+        Ok(())
+    }
+
+    /// Returns where the cursor moves one screen row down or up, which is a line unless lines
+    /// wrap, keeping the screen column
+    fn get_row_target(&self, from: Coords, down: bool) -> anyhow::Result<Coords> {
+        let (cursor_x, cursor_y) = (from.x.min(self.get_line_len(from.y)?), from.y);
+        let layout = self.get_layout(cursor_y);
+        let (row, column) = layout.get_position(cursor_x);
+        let show_break_width = self.get_show_break().width();
+
+        let column = match row {
+            0 => column,
+            _ => column + show_break_width,
+        };
+
+        let (y, row) = match down {
+            true if row + 1 < layout.len_rows() => (cursor_y, row + 1),
+            true if cursor_y + 1 < self.content().len_lines() => (cursor_y + 1, 0),
+            false if row > 0 => (cursor_y, row - 1),
+            false if cursor_y > 0 => (cursor_y - 1, self.get_layout(cursor_y - 1).len_rows() - 1),
+            _ => return Ok(from),
+        };
+
+        let column = match row {
+            0 => column,
+            _ => column.saturating_sub(show_break_width),
+        };
+
+        Ok(Coords {
+            x: self.get_layout(y).get_x(row, column),
+            y,
+        })
+    }
+
+    fn handle_key_inner(&mut self, event: &KeyEvent, count: Option<usize>) -> anyhow::Result<()> {
+        match (event.modifiers, event.code, &self.mode) {
+            // Global movement
+            (
+                KeyModifiers::NONE,
+                code @ (KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down),
+                _,
+            ) => {
+                let motion = match code {
+                    KeyCode::Left => Motion::Left,
+                    KeyCode::Right => Motion::Right,
+                    KeyCode::Up => Motion::Up,
+                    _ => Motion::Down,
+                };
+
+                // Like vim, moving the cursor in insert mode starts a new undo step
+                if matches!(self.mode, Mode::Insert | Mode::Replace) {
+                    self.document.borrow_mut().commit();
                 }
+
+                self.move_cursor(motion, count)?;
+            }
+
+            // Normal mode
+            (KeyModifiers::CONTROL, KeyCode::Char('r'), Mode::Normal) => {
+                self.execute(NormalCommand {
+                    count,
+                    action: Action::Redo,
+                })?;
             }
 
             // Insert mode
             (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c), Mode::Insert) => {
                 self.add_char(c)?;
             }
-            (KeyModifiers::NONE, KeyCode::Esc, Mode::Insert | Mode::Command) => {
+            (KeyModifiers::NONE, KeyCode::Esc, Mode::Insert) => {
                 self.mode = Mode::Normal;
             }
             (KeyModifiers::NONE, KeyCode::Enter, Mode::Insert) => {
@@ -410,6 +1085,46 @@ impl TextBlock {
             (KeyModifiers::NONE, KeyCode::Backspace, Mode::Insert) => {
                 self.delete()?;
             }
+
+            // Replace mode
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c), Mode::Replace) => {
+                let (x, y) = self.get_cursor_pos()?;
+                let replaced = self.content().line(y).and_then(|line| line.get_char(x));
+
+                match replaced {
+                    Some(_) => self.replace_char(c)?,
+                    None => self.add_char(c)?,
+                };
+
+                self.set_cursor_x(x + 1)?;
+                self.replaced.push(replaced);
+            }
+            (KeyModifiers::NONE, KeyCode::Esc, Mode::Replace) => {
+                self.mode = Mode::Normal;
+            }
+            (KeyModifiers::NONE, KeyCode::Enter, Mode::Replace) => {
+                self.new_line()?;
+                self.replaced.clear();
+            }
+            (KeyModifiers::NONE, KeyCode::Backspace, Mode::Replace) => {
+                let (x, _) = self.get_cursor_pos()?;
+
+                if x == 0 {
+                    return Ok(());
+                }
+
+                self.set_cursor_x(x - 1)?;
+
+                match self.replaced.pop() {
+                    Some(Some(c)) => self.replace_char(c)?,
+                    Some(None) => {
+                        self.set_cursor_x(x)?;
+                        self.delete()?;
+                    }
+                    None => {}
+                };
+            }
+
             _ => (),
         };
 
@@ -418,19 +1133,16 @@ impl TextBlock {
 
     pub fn new_line(&mut self) -> anyhow::Result<()> {
         let (cursor_x, cursor_y) = self.get_cursor_pos()?;
-        let line = self.get_line_at_mut(cursor_y)?;
 
-        let new_line = if cursor_x < line.len_chars() {
-            line.try_split_off(cursor_x)?
-        } else {
-            Rope::new()
-        };
+        self.document.borrow_mut().insert(
+            (cursor_x, cursor_y).into(),
+            "\n",
+            (cursor_x, cursor_y).into(),
+        )?;
 
         self.cursor.y += 1;
         self.cursor.x = 0;
 
-        self.content.push_at(cursor_y + 1, new_line);
-
         Ok(())
     }
 
@@ -441,12 +1153,15 @@ impl TextBlock {
         let (mut new_cursor_x, new_cursor_y) = self.subtract_cursor_pos()?;
 
         if cursor_x > 0 {
-            let last_line = self.get_line_at_mut(cursor_y)?;
-            last_line.try_remove(cursor_index.saturating_sub(1)..cursor_index)?;
+            self.document.borrow_mut().remove(
+                (cursor_index - 1, cursor_y).into(),
+                1,
+                (cursor_x, cursor_y).into(),
+            )?;
         };
 
         if new_cursor_y < cursor_y {
-            new_cursor_x = self.get_line_at(new_cursor_y)?.len_chars();
+            new_cursor_x = self.get_line_len(new_cursor_y)?;
             self.append_to_prev_line()?;
         }
 
@@ -499,13 +1214,11 @@ impl TextBlock {
             return Ok(());
         }
 
-        let removed_line = self.content.remove_at(cursor_y).ok_or(anyhow::anyhow!(
-            "Could not remove line at index {}",
-            cursor_y
-        ))?;
-        let prev_line = self.get_line_at_mut(cursor_y.saturating_sub(1))?;
+        let prev_line_len = self.get_line_len(cursor_y - 1)?;
 
-        prev_line.append(removed_line);
+        self.document
+            .borrow_mut()
+            .remove((prev_line_len, cursor_y - 1).into(), 1, self.cursor)?;
 
         Ok(())
     }
@@ -513,8 +1226,7 @@ impl TextBlock {
     pub fn get_cursor_pos(&self) -> anyhow::Result<(usize, usize)> {
         let raw_pos = &self.cursor;
 
-        let line = self.get_line_at(raw_pos.y)?;
-        let x = raw_pos.x.min(line.len_chars());
+        let x = raw_pos.x.min(self.get_line_len(raw_pos.y)?);
 
         Ok((x, raw_pos.y))
     }
@@ -522,12 +1234,369 @@ impl TextBlock {
     pub fn add_char(&mut self, c: char) -> anyhow::Result<()> {
         let (cursor_index, line_index) = self.get_cursor_pos()?;
 
-        let last_line = self.get_line_at_mut(line_index)?;
-
-        last_line.try_insert_char(cursor_index, c)?;
+        self.document.borrow_mut().insert(
+            (cursor_index, line_index).into(),
+            c.encode_utf8(&mut [0; 4]),
+            (cursor_index, line_index).into(),
+        )?;
 
         self.set_cursor_x(cursor_index + 1)?;
 
         Ok(())
     }
+
+    /// Replaces the char under the cursor with `c`, leaving the cursor where it is
+    fn replace_char(&mut self, c: char) -> anyhow::Result<()> {
+        let (x, y) = self.get_cursor_pos()?;
+
+        if x >= self.get_line_len(y)? {
+            return Ok(());
+        }
+
+        let mut document = self.document.borrow_mut();
+
+        document.remove((x, y).into(), 1, (x, y).into())?;
+        document.insert((x, y).into(), c.encode_utf8(&mut [0; 4]), (x, y).into())?;
+
+        Ok(())
+    }
+
+    fn restore_cursor(&mut self, cursor: Coords) -> anyhow::Result<()> {
+        self.goto_line(cursor.y);
+        self.cursor.x = cursor.x.min(self.get_line_len(self.cursor.y)?);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{assert_snapshot, get_active_view, get_text, run};
+
+    async fn check(name: &str, content: &str, keys: &str) {
+        let editor = run(content, keys).await;
+
+        assert_snapshot(name, keys, &editor);
+    }
+
+    #[tokio::test]
+    async fn insert_text() {
+        check("insert_text", "", "iHello<CR>world<Esc>").await;
+    }
+
+    #[tokio::test]
+    async fn backspace_joins_lines() {
+        check("backspace_joins_lines", "one\ntwo", "ji<BS><BS>x<Esc>").await;
+    }
+
+    #[tokio::test]
+    async fn word_motions() {
+        check("word_motions", "let x = foo(bar);\nnext line", "wwwe").await;
+    }
+
+    #[tokio::test]
+    async fn word_motions_cross_lines() {
+        check("word_motions_cross_lines", "one two\nthree", "wwwbb").await;
+    }
+
+    #[tokio::test]
+    async fn edit_after_word_motion() {
+        check("edit_after_word_motion", "", "iHello world<Esc>bix<Esc>").await;
+    }
+
+    #[tokio::test]
+    async fn undo_and_redo() {
+        check("undo_and_redo", "", "ione<Esc>i two<Esc>uu<C-r>").await;
+    }
+
+    #[tokio::test]
+    async fn arrows_split_insert_undo_steps() {
+        let editor = run("", "ifoo<Left><Left>X<Esc>u").await;
+
+        assert_eq!(get_text(&editor), "foo");
+        assert_eq!(get_active_view(&editor).get_cursor(), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn replace_mode() {
+        check("replace_mode", "abc", "Rxyzw<BS><BS><Esc>").await;
+    }
+
+    #[tokio::test]
+    async fn replace_char() {
+        check("replace_char", "abc", "lrx").await;
+    }
+
+    #[tokio::test]
+    async fn line_numbers() {
+        check("line_numbers", "one\ntwo\nthree", ":set nu<CR>jl").await;
+    }
+
+    #[tokio::test]
+    async fn relative_line_numbers() {
+        check("relative_line_numbers", "one\ntwo\nthree", ":set rnu<CR>j").await;
+    }
+
+    #[tokio::test]
+    async fn hybrid_line_numbers() {
+        check("hybrid_line_numbers", "one\ntwo\nthree", ":set nu rnu<CR>j").await;
+    }
+
+    #[tokio::test]
+    async fn line_numbers_scroll_long_lines() {
+        let line = "abcdefghij".repeat(4);
+
+        check(
+            "line_numbers_scroll_long_lines",
+            &line,
+            ":set number<CR>eeee",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn wrap_long_lines() {
+        let text = format!("{}\nshort", "abcdefghij".repeat(5));
+
+        let editor = run(&text, ":set wrap nu<CR>lgj").await;
+
+        assert_snapshot("wrap_long_lines", ":set wrap nu<CR>lgj", &editor);
+        assert_eq!(editor.backend.get_cursor(), (5, 1));
+    }
+
+    #[tokio::test]
+    async fn wrap_at_word_boundaries() {
+        let text = "The quick brown fox jumps over the lazy dog and keeps running";
+
+        check(
+            "wrap_at_word_boundaries",
+            text,
+            ":set wrap lbr sbr=>\\ <CR>",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn move_by_screen_rows() {
+        let text = format!("{}\nshort", "abcdefghij".repeat(9));
+
+        check("move_by_screen_rows", &text, ":set wrap<CR>lllgjgjgjgkj").await;
+    }
+
+    #[tokio::test]
+    async fn wrap_scrolls_by_rows() {
+        let text = format!("{}\n", "abcdefghij".repeat(9)).repeat(3);
+
+        let editor = run(&text, ":set wrap<CR>jjgjgj").await;
+
+        assert_snapshot("wrap_scrolls_by_rows", ":set wrap<CR>jjgjgj", &editor);
+        assert_eq!(editor.backend.get_cursor(), (0, 6));
+    }
+
+    #[tokio::test]
+    async fn tabs_expand_to_tab_stops() {
+        let editor = run("a\tb\n\tc", "ll").await;
+
+        assert_snapshot("tabs_expand_to_tab_stops", "ll", &editor);
+        assert_eq!(editor.backend.get_cursor(), (8, 0));
+
+        let editor = run("a\tb\n\tc", "l").await;
+
+        assert_eq!(editor.backend.get_cursor(), (7, 0));
+
+        let editor = run("a\tb\n\tc", "li").await;
+
+        assert_eq!(editor.backend.get_cursor(), (1, 0));
+
+        let editor = run("a\tb\n\tc", ":set ts=4<CR>ll").await;
+
+        assert_eq!(editor.backend.get_lines()[..2], ["a   b", "    c"]);
+        assert_eq!(editor.backend.get_cursor(), (4, 0));
+    }
+
+    #[tokio::test]
+    async fn wide_chars_take_two_columns() {
+        let editor = run("中文abc\n한국어", "llj").await;
+
+        assert_snapshot("wide_chars_take_two_columns", "llj", &editor);
+        assert_eq!(editor.backend.get_cursor(), (4, 1));
+    }
+
+    #[tokio::test]
+    async fn vertical_motions_keep_the_display_column() {
+        let editor = run("中文abc\nabcdefg", "lllj").await;
+
+        assert_eq!(editor.backend.get_cursor(), (5, 1));
+
+        let editor = run("abc\ne\u{301}x", "ljiZ<Esc>").await;
+
+        assert_eq!(get_text(&editor), "abc\ne\u{301}Zx");
+
+        // The column is kept across shorter lines, and `$` sticks to the end of the lines
+        let editor = run("abcdef\nab\nabcdef", "4ljj").await;
+
+        assert_eq!(get_active_view(&editor).get_cursor(), (4, 2));
+
+        let editor = run("ab\nabcdef\nabc", "$j").await;
+
+        assert_eq!(get_active_view(&editor).get_cursor(), (5, 1));
+
+        let editor = run("ab\nabcdef\nabc", "$jj").await;
+
+        assert_eq!(get_active_view(&editor).get_cursor(), (2, 2));
+
+        let editor = run("abcdef\nab\nabcdef", "4ljhj").await;
+
+        assert_eq!(get_active_view(&editor).get_cursor(), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn grapheme_clusters_are_single_cursor_stops() {
+        let editor = run("e\u{301}👍🏽x", "ll").await;
+
+        assert_snapshot("grapheme_clusters_are_single_cursor_stops", "ll", &editor);
+        assert_eq!(editor.backend.get_cursor(), (3, 0));
+    }
+
+    #[tokio::test]
+    async fn wide_chars_wrap_whole() {
+        let text = format!("{}中文", "a".repeat(39));
+        let editor = run(&text, ":set wrap<CR>").await;
+
+        assert_snapshot("wide_chars_wrap_whole", ":set wrap<CR>", &editor);
+    }
+
+    #[tokio::test]
+    async fn word_motions_on_unicode_text() {
+        check(
+            "word_motions_on_unicode_text",
+            "naïve café, 日本語テキスト x.y\nnext",
+            "wwwwBEeW",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn line_motions() {
+        check("line_motions", "  let x = 1;", "$i!<Esc>^i#<Esc>0i0<Esc>").await;
+    }
+
+    #[tokio::test]
+    async fn goto_lines() {
+        let text = (1..=12)
+            .map(|n| format!("  line {}\n", n))
+            .collect::<String>();
+
+        check(
+            "goto_lines",
+            &text,
+            "GiG<Esc>10Gi10<Esc>ggigg<Esc>3ggi3<Esc>",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn char_search_motions() {
+        check(
+            "char_search_motions",
+            "a,b,c,d,e",
+            "f,;i1<Esc>tei2<Esc>0t,;i3<Esc>$F,,i4<Esc>",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn bracket_paragraph_and_sentence_motions() {
+        check(
+            "bracket_paragraph_and_sentence_motions",
+            "fn f(a[0]) {\n}\n\nOne. Two!\nThree.",
+            "%i1<Esc>})i2<Esc>)i3<Esc>(i4<Esc>{{i5<Esc>",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn screen_line_motions() {
+        let text = (1..=20)
+            .map(|n| format!("line {}\n", n))
+            .collect::<String>();
+
+        check("screen_line_motions", &text, "GHiH<Esc>MiM<Esc>LiL<Esc>").await;
+    }
+
+    #[tokio::test]
+    async fn counts_repeat_motions() {
+        check(
+            "counts_repeat_motions",
+            "one two three four\nfive\nsix\nseven",
+            "3wi1<Esc>2ji2<Esc>2ki3<Esc>02li4<Esc>",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn counts_apply_to_arrows_and_redo() {
+        check(
+            "counts_apply_to_arrows",
+            "one\ntwo\nthree\nfour\nfive",
+            "3<Down>2<Right>ix<Esc>",
+        )
+        .await;
+        check(
+            "counts_apply_to_redo",
+            "",
+            "ione<Esc>itwo<Esc>ithree<Esc>2u2<C-r>",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn delete_with_motions() {
+        check(
+            "delete_with_motions",
+            "one two three four\nfive six\nseven",
+            "d2wwdwjd$",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn delete_lines() {
+        check("delete_lines", "one\ntwo\nthree\nfour\nfive", "jddj2ddu").await;
+    }
+
+    #[tokio::test]
+    async fn delete_last_lines() {
+        check("delete_last_lines", "one\ntwo\nthree", "jd2j").await;
+    }
+
+    #[tokio::test]
+    async fn change_word_and_line() {
+        check(
+            "change_word_and_line",
+            "let foo = bar;\nold line",
+            "wcwbaz<Esc>jccnew<Esc>",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn change_inside_brackets() {
+        check(
+            "change_inside_brackets",
+            "call(one, two)\nif x {\n    old;\n}",
+            "fnci(1<Esc>jjci{new<Esc>",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn delete_around_words_and_till_chars() {
+        check(
+            "delete_around_words_and_till_chars",
+            "one two three, four",
+            "wdawdt,",
+        )
+        .await;
+    }
 }
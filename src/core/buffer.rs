@@ -0,0 +1,119 @@
+use std::fmt::Display;
+
+use regex::Regex;
+use ropey::{
+    iter::{Chars, Chunks},
+    Rope, RopeSlice,
+};
+
+use crate::core::Coords;
+
+/// The text of a document, stored as a single rope with `\n` separated lines. Positions are
+/// `(x, y)` coordinates where `x` is a char index within line `y`.
+#[derive(Clone, Default)]
+pub struct Buffer(Rope);
+
+impl From<&str> for Buffer {
+    fn from(text: &str) -> Self {
+        Self(Rope::from_str(text))
+    }
+}
+
+impl Display for Buffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Buffer {
+    /// Returns the number of lines, which is always at least one
+    pub fn len_lines(&self) -> usize {
+        self.0.len_lines()
+    }
+
+    /// Returns the line at `index`, without its line break
+    pub fn line(&self, index: usize) -> Option<RopeSlice<'_>> {
+        let line = self.0.get_line(index)?;
+
+        match line.len_chars().checked_sub(1) {
+            Some(last) if line.char(last) == '\n' => Some(line.slice(..last)),
+            _ => Some(line),
+        }
+    }
+
+    pub fn line_len(&self, index: usize) -> Option<usize> {
+        self.line(index).map(|line| line.len_chars())
+    }
+
+    /// Returns the position of the first match of `pattern`. Matches can't span several lines.
+    pub fn find(&self, pattern: &Regex) -> Option<Coords> {
+        (0..self.len_lines()).find_map(|y| {
+            let line = self.line(y)?.to_string();
+            let start = pattern.find(&line)?.start();
+
+            Some(Coords {
+                x: line[..start].chars().count(),
+                y,
+            })
+        })
+    }
+
+    /// Returns an iterator over the chars, line breaks included, positioned right before the char
+    /// at the absolute index `index`. It can move both ways, backwards with `prev`.
+    pub fn chars_at(&self, index: usize) -> Chars<'_> {
+        self.0.chars_at(index.min(self.0.len_chars()))
+    }
+
+    /// Converts an absolute char index into a position, the reverse of `char_index`
+    pub fn coords(&self, index: usize) -> Coords {
+        let index = index.min(self.0.len_chars());
+        let y = self.0.char_to_line(index);
+
+        Coords {
+            x: index - self.0.line_to_char(y),
+            y,
+        }
+    }
+
+    pub fn chunks(&self) -> Chunks<'_> {
+        self.0.chunks()
+    }
+
+    /// Converts a position into an absolute char index. `x` may be at most the line length,
+    /// pointing right before the line break.
+    pub fn char_index(&self, at: Coords) -> anyhow::Result<usize> {
+        let line_len = self
+            .line_len(at.y)
+            .ok_or(anyhow::anyhow!("No line at index {}", at.y))?;
+
+        if at.x > line_len {
+            anyhow::bail!("No char at index {} in line {}", at.x, at.y);
+        }
+
+        Ok(self.0.try_line_to_char(at.y)? + at.x)
+    }
+
+    pub fn insert(&mut self, at: Coords, text: &str) -> anyhow::Result<()> {
+        let index = self.char_index(at)?;
+
+        self.0.try_insert(index, text)?;
+
+        Ok(())
+    }
+
+    /// Removes `len` chars starting at `at`, line breaks included, returning the removed text
+    pub fn remove(&mut self, at: Coords, len: usize) -> anyhow::Result<String> {
+        let start = self.char_index(at)?;
+        let end = start + len;
+
+        let removed = self
+            .0
+            .get_slice(start..end)
+            .ok_or(anyhow::anyhow!("Could not remove chars {}..{}", start, end))?
+            .to_string();
+
+        self.0.try_remove(start..end)?;
+
+        Ok(removed)
+    }
+}
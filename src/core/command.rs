@@ -0,0 +1,414 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::core::options::SetArgument;
+
+/// An ex command entered in the command line
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Set {
+        arguments: Vec<SetArgument>,
+    },
+    Write {
+        file_path: Option<String>,
+        create_dirs: bool,
+    },
+    WriteQuit {
+        file_path: Option<String>,
+        create_dirs: bool,
+    },
+    SaveAs {
+        file_path: String,
+        create_dirs: bool,
+    },
+    Quit {
+        force: bool,
+    },
+    QuitAll {
+        force: bool,
+    },
+    Split,
+    VerticalSplit,
+    ListBuffers,
+    NextBuffer,
+    PreviousBuffer,
+    /// Shows the buffer matching `target`, either its number or part of its name
+    Buffer {
+        target: String,
+    },
+    /// Unloads the buffer matching `target`, or the current one
+    DeleteBuffer {
+        target: Option<String>,
+        force: bool,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandError {
+    Empty,
+    NotAnEditorCommand(String),
+    ArgumentRequired,
+    TrailingCharacters(String),
+    UnknownOption(String),
+    InvalidArgument(String),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::Empty => write!(f, "Empty command"),
+            CommandError::NotAnEditorCommand(command) => {
+                write!(f, "Not an editor command: {}", command)
+            }
+            CommandError::ArgumentRequired => write!(f, "Argument required"),
+            CommandError::TrailingCharacters(arg) => write!(f, "Trailing characters: {}", arg),
+            CommandError::UnknownOption(option) => write!(f, "Unknown option: {}", option),
+            CommandError::InvalidArgument(arg) => write!(f, "Invalid argument: {}", arg),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl FromStr for Command {
+    type Err = CommandError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        // Like vim, leading colons are ignored, so `-c :set ff=dos` works too
+        let input = trim_end_unescaped(input)
+            .trim_start()
+            .trim_start_matches(':')
+            .trim_start();
+
+        if input.is_empty() {
+            return Err(CommandError::Empty);
+        }
+
+        let (name, bang, arg) = split_command(input);
+        let (create_dirs, file_path) = split_write_options(arg);
+
+        let is = |command| is_abbreviation(name, command);
+
+        let command = match name {
+            _ if is("w[rite]") => Command::Write {
+                file_path,
+                create_dirs,
+            },
+            _ if is("wq") || is("x[it]") => Command::WriteQuit {
+                file_path,
+                create_dirs,
+            },
+            _ if is("sav[eas]") => Command::SaveAs {
+                file_path: file_path.ok_or(CommandError::ArgumentRequired)?,
+                create_dirs,
+            },
+            _ if is("q[uit]") => Command::Quit { force: bang },
+            _ if is("qa[ll]") || is("quita[ll]") => Command::QuitAll { force: bang },
+            _ if is("se[t]") => Command::Set {
+                arguments: split_set_arguments(arg.ok_or(CommandError::ArgumentRequired)?)
+                    .iter()
+                    .map(|argument| argument.parse())
+                    .collect::<Result<_, _>>()?,
+            },
+            _ if is("sp[lit]") => Command::Split,
+            _ if is("vs[plit]") => Command::VerticalSplit,
+            _ if is("ls") || is("buffers") || is("files") => Command::ListBuffers,
+            _ if is("bn[ext]") => Command::NextBuffer,
+            _ if is("bp[revious]") || is("bN[ext]") => Command::PreviousBuffer,
+            _ if is("b[uffer]") => Command::Buffer {
+                target: arg.ok_or(CommandError::ArgumentRequired)?.to_owned(),
+            },
+            _ if is("bd[elete]") => Command::DeleteBuffer {
+                target: arg.map(str::to_owned),
+                force: bang,
+            },
+            _ => return Err(CommandError::NotAnEditorCommand(input.to_owned())),
+        };
+
+        match (&command, arg) {
+            (
+                Command::Quit { .. }
+                | Command::QuitAll { .. }
+                | Command::Split
+                | Command::VerticalSplit
+                | Command::ListBuffers
+                | Command::NextBuffer
+                | Command::PreviousBuffer,
+                Some(arg),
+            ) => Err(CommandError::TrailingCharacters(arg.to_owned())),
+            (
+                Command::Write { .. }
+                | Command::WriteQuit { .. }
+                | Command::SaveAs { .. }
+                | Command::Set { .. }
+                | Command::Split
+                | Command::VerticalSplit
+                | Command::ListBuffers
+                | Command::NextBuffer
+                | Command::PreviousBuffer
+                | Command::Buffer { .. },
+                _,
+            ) if bang => Err(CommandError::TrailingCharacters("!".to_owned())),
+            _ => Ok(command),
+        }
+    }
+}
+
+/// Returns whether `name` is `command` or one of its abbreviations. Like in vim's help, `command`
+/// is written as its shortest abbreviation followed by the optional rest in brackets, as in
+/// `w[rite]`, which matches `w`, `wr` and up to `write`.
+fn is_abbreviation(name: &str, command: &str) -> bool {
+    let (short, rest) = match command.split_once('[') {
+        Some((short, rest)) => (short, rest.trim_end_matches(']')),
+        None => (command, ""),
+    };
+
+    name.strip_prefix(short)
+        .is_some_and(|typed| rest.starts_with(typed))
+}
+
+/// Splits the command name from its `!` modifier and its argument. The name is either a run of
+/// letters or a single symbol, so both `w foo` and `w!` are split at the right place.
+fn split_command(input: &str) -> (&str, bool, Option<&str>) {
+    let name_end = input
+        .char_indices()
+        .find(|(_, c)| !c.is_ascii_alphabetic())
+        .map(|(i, _)| i)
+        .unwrap_or(input.len());
+
+    let name_end = match name_end {
+        0 => input.chars().next().map(char::len_utf8).unwrap_or(0),
+        name_end => name_end,
+    };
+
+    let (name, rest) = input.split_at(name_end);
+
+    let (bang, arg) = match rest.strip_prefix('!') {
+        Some(arg) => (true, arg.trim_start()),
+        None => (false, rest.trim_start()),
+    };
+
+    (name, bang, Some(arg).filter(|arg| !arg.is_empty()))
+}
+
+/// Trims the whitespace at the end of `input`, but keeps a space escaped as `\ ` so values like
+/// `:set showbreak=>\ ` can end with one
+fn trim_end_unescaped(input: &str) -> &str {
+    let trimmed = input.trim_end();
+    let backslashes = trimmed.chars().rev().take_while(|c| *c == '\\').count();
+
+    match input[trimmed.len()..].starts_with(' ') && !backslashes.is_multiple_of(2) {
+        true => &input[..trimmed.len() + 1],
+        false => trimmed,
+    }
+}
+
+/// Splits the arguments of `:set` at whitespace. Like in vim, a space can be part of a value by
+/// escaping it as `\ `, and a backslash by escaping it as `\\`.
+fn split_set_arguments(arg: &str) -> Vec<String> {
+    let mut arguments = vec![];
+    let mut argument = String::new();
+    let mut chars = arg.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars
+                .peek()
+                .is_some_and(|next| *next == ' ' || *next == '\\') =>
+            {
+                argument.extend(chars.next());
+            }
+            c if c.is_whitespace() => {
+                if !argument.is_empty() {
+                    arguments.push(std::mem::take(&mut argument));
+                }
+            }
+            c => argument.push(c),
+        }
+    }
+
+    if !argument.is_empty() {
+        arguments.push(argument);
+    }
+
+    arguments
+}
+
+/// Splits the `++p` option of the write commands from the file path. Like in vim, it creates the
+/// missing parent directories of the written file. Spaces in the path are escaped as `\ `.
+fn split_write_options(arg: Option<&str>) -> (bool, Option<String>) {
+    let (create_dirs, file_path) = match arg {
+        Some("++p") => (true, None),
+        Some(arg) => match arg.strip_prefix("++p ") {
+            Some(file_path) => (true, Some(file_path.trim_start())),
+            None => (false, Some(arg)),
+        },
+        None => (false, None),
+    };
+
+    (
+        create_dirs,
+        file_path.map(|file_path| file_path.replace("\\ ", " ")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::options::{OptionName, SetAction};
+
+    fn parse(input: &str) -> Result<Command, CommandError> {
+        input.parse()
+    }
+
+    fn write(file_path: Option<&str>, create_dirs: bool) -> Command {
+        Command::Write {
+            file_path: file_path.map(str::to_owned),
+            create_dirs,
+        }
+    }
+
+    #[test]
+    fn commands_can_be_abbreviated() {
+        for input in ["w", "wr", "wri", "write", ":w", "  :: w  "] {
+            assert_eq!(parse(input), Ok(write(None, false)), "{}", input);
+        }
+
+        assert_eq!(parse("sp"), Ok(Command::Split));
+        assert_eq!(parse("spl"), Ok(Command::Split));
+        assert_eq!(parse("vs"), Ok(Command::VerticalSplit));
+        assert_eq!(parse("vsplit"), Ok(Command::VerticalSplit));
+        assert_eq!(parse("quitall"), Ok(Command::QuitAll { force: false }));
+        assert_eq!(parse("bN"), Ok(Command::PreviousBuffer));
+        assert_eq!(
+            parse("b 2"),
+            Ok(Command::Buffer {
+                target: "2".to_owned()
+            })
+        );
+        assert_eq!(
+            parse("bd"),
+            Ok(Command::DeleteBuffer {
+                target: None,
+                force: false
+            })
+        );
+        assert_eq!(
+            parse("bdel main.rs"),
+            Ok(Command::DeleteBuffer {
+                target: Some("main.rs".to_owned()),
+                force: false
+            })
+        );
+
+        for input in ["wx", "writes", "s", "bx", "foo"] {
+            assert_eq!(
+                parse(input),
+                Err(CommandError::NotAnEditorCommand(input.to_owned()))
+            );
+        }
+
+        assert_eq!(parse(""), Err(CommandError::Empty));
+        assert_eq!(parse(":"), Err(CommandError::Empty));
+    }
+
+    #[test]
+    fn bang_forces_the_commands_that_take_it() {
+        assert_eq!(parse("q!"), Ok(Command::Quit { force: true }));
+        assert_eq!(parse("qa!"), Ok(Command::QuitAll { force: true }));
+        assert_eq!(
+            parse("bd! 2"),
+            Ok(Command::DeleteBuffer {
+                target: Some("2".to_owned()),
+                force: true
+            })
+        );
+
+        for input in ["w!", "sp!", "ls!", "set! nu"] {
+            assert_eq!(
+                parse(input),
+                Err(CommandError::TrailingCharacters("!".to_owned())),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn write_commands_take_a_path_and_options() {
+        assert_eq!(parse("w ++p"), Ok(write(None, true)));
+        assert_eq!(
+            parse("w ++p  dir/a.txt"),
+            Ok(write(Some("dir/a.txt"), true))
+        );
+        assert_eq!(parse("w ++pa.txt"), Ok(write(Some("++pa.txt"), false)));
+        assert_eq!(
+            parse("w my\\ file.txt"),
+            Ok(write(Some("my file.txt"), false))
+        );
+        assert_eq!(parse("w a\\ "), Ok(write(Some("a "), false)));
+        assert_eq!(
+            parse("x a.txt"),
+            Ok(Command::WriteQuit {
+                file_path: Some("a.txt".to_owned()),
+                create_dirs: false
+            })
+        );
+        assert_eq!(
+            parse("sav ++p dir/a.txt"),
+            Ok(Command::SaveAs {
+                file_path: "dir/a.txt".to_owned(),
+                create_dirs: true
+            })
+        );
+        assert_eq!(parse("saveas"), Err(CommandError::ArgumentRequired));
+        assert_eq!(parse("saveas ++p"), Err(CommandError::ArgumentRequired));
+        assert_eq!(parse("b"), Err(CommandError::ArgumentRequired));
+    }
+
+    #[test]
+    fn commands_without_arguments_refuse_them() {
+        for (input, arg) in [("q now", "now"), ("sp a.txt", "a.txt"), ("bn 2", "2")] {
+            assert_eq!(
+                parse(input),
+                Err(CommandError::TrailingCharacters(arg.to_owned()))
+            );
+        }
+    }
+
+    #[test]
+    fn set_arguments_are_split_at_unescaped_spaces() {
+        assert_eq!(
+            parse("set nu sbr=>\\ \\\\ ts=4"),
+            Ok(Command::Set {
+                arguments: vec![
+                    SetArgument {
+                        option: OptionName::Number,
+                        action: SetAction::Enable
+                    },
+                    SetArgument {
+                        option: OptionName::ShowBreak,
+                        action: SetAction::Assign("> \\".to_owned())
+                    },
+                    SetArgument {
+                        option: OptionName::TabStop,
+                        action: SetAction::Assign("4".to_owned())
+                    },
+                ]
+            })
+        );
+        assert_eq!(
+            parse("set sbr=>\\ "),
+            Ok(Command::Set {
+                arguments: vec![SetArgument {
+                    option: OptionName::ShowBreak,
+                    action: SetAction::Assign("> ".to_owned())
+                }]
+            })
+        );
+        assert_eq!(parse("set"), Err(CommandError::ArgumentRequired));
+        assert_eq!(
+            parse("set nu foo"),
+            Err(CommandError::UnknownOption("foo".to_owned()))
+        );
+    }
+}
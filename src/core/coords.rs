@@ -1,8 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Coords {
     pub x: usize,
     pub y: usize,
 }
 
+#[derive(Clone, Copy)]
 pub struct TermScreenCoords {
     pub x: u16,
     pub y: u16,
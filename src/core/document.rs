@@ -1,36 +1,683 @@
-use std::path::Path;
+use std::{
+    cell::RefCell,
+    io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
-use ropey::Rope;
+use tokio::io::AsyncReadExt;
 
-use crate::core::extended_linked_list::ExtendedLinkedList;
+use crate::core::{
+    encoding,
+    file_format::{FileFormat, LineEnding},
+    history::{Edit, Revision},
+    layout, undo_file, Buffer, Coords, History,
+};
+
+/// A document shared between the editor and every view showing it
+pub type DocumentHandle = Rc<RefCell<Document>>;
 
 pub struct Document {
     file_path: Option<String>,
     dirty: bool,
-    pub content: ExtendedLinkedList<Rope>,
+    content: Buffer,
+    history: History,
+    /// The history node matching the content on disk, to tell whether undoing or redoing
+    /// brings the document back to it
+    saved_node: usize,
+    /// Whether the encoding or the line ending changed since the last write
+    format_changed: bool,
+    format: FileFormat,
+    binary: bool,
+    read_only: bool,
+    new_file: bool,
+    last_cursor: Coords,
+    /// The number of columns between tab stops, set with `:set tabstop`
+    tab_stop: usize,
+}
+
+pub struct WriteStats {
+    pub file_path: String,
+    /// Whether the document moves to `file_path`, like with `:saveas`
+    pub rename: bool,
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+impl From<&str> for Document {
+    /// Creates an unnamed document holding `text`
+    fn from(text: &str) -> Self {
+        Self::from_text(None, text, FileFormat::default())
+    }
 }
 
 impl Document {
     pub async fn new(file_path: Option<String>) -> std::io::Result<Self> {
-        let content = match file_path.as_deref() {
-            Some(path) => ExtendedLinkedList::from_vec(
-                tokio::fs::read_to_string(Path::new(path))
-                    .await?
-                    .lines()
-                    .map(Rope::from)
-                    .collect::<Vec<_>>(),
-            ),
-            None => ExtendedLinkedList::from([Rope::new()]),
+        let Some(path) = file_path.as_deref() else {
+            return Ok(Self::empty());
+        };
+
+        if path == "-" {
+            return Self::from_stdin().await;
+        }
+
+        // A path that doesn't exist yet opens an empty document, the file is created on write
+        let bytes = match tokio::fs::read(Path::new(path)).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut document = Self::from_text(file_path, "", FileFormat::default());
+
+                document.new_file = true;
+
+                return Ok(document);
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Binary files are shown as a read-only hex dump rather than garbled text
+        let Some((format, text)) = FileFormat::detect(&bytes)? else {
+            let mut document = Self::from_text(file_path, &hex_dump(&bytes), FileFormat::default());
+
+            document.binary = true;
+            document.read_only = true;
+
+            return Ok(document);
+        };
+
+        let mut document = Self::from_text(file_path.clone(), &text, format);
+
+        let hash = undo_file::hash_content(&bytes);
+
+        if let Some(history) = undo_file::load(Path::new(path), hash).await {
+            document.saved_node = history.get_current();
+            document.history = history;
+        }
+
+        Ok(document)
+    }
+
+    /// Reads all of stdin into an unnamed document. The content isn't marked as modified since it
+    /// can be read again by running the command again.
+    async fn from_stdin() -> io::Result<Self> {
+        let mut bytes = vec![];
+
+        tokio::io::stdin().read_to_end(&mut bytes).await?;
+
+        let document = match FileFormat::detect(&bytes)? {
+            Some((format, text)) => Self::from_text(None, &text, format),
+            None => {
+                let mut document = Self::from_text(None, &hex_dump(&bytes), FileFormat::default());
+
+                document.binary = true;
+                document.read_only = true;
+
+                document
+            }
         };
 
-        Ok(Self {
+        Ok(document)
+    }
+
+    /// Creates an unnamed document with no content
+    pub fn empty() -> Self {
+        Self::from_text(None, "", FileFormat::default())
+    }
+
+    fn from_text(file_path: Option<String>, text: &str, format: FileFormat) -> Self {
+        Self {
             file_path,
-            content,
+            content: Buffer::from(text),
+            history: History::new(),
+            saved_node: 0,
+            format_changed: false,
+            format,
+            binary: false,
+            read_only: false,
+            new_file: false,
+            last_cursor: (0, 0).into(),
+            tab_stop: layout::DEFAULT_TAB_STOP,
             dirty: false,
-        })
+        }
     }
 
-    pub fn get_content(&self) -> &ExtendedLinkedList<Rope> {
+    pub fn get_content(&self) -> &Buffer {
         &self.content
     }
+
+    pub fn get_file_path(&self) -> Option<&str> {
+        self.file_path.as_deref()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Closes the change being built, so later edits are undone separately
+    pub fn commit(&mut self) {
+        self.history.commit();
+    }
+
+    pub fn get_format(&self) -> FileFormat {
+        self.format
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Prevents any modification of the document and writing it back to its file
+    pub fn set_read_only(&mut self) {
+        self.read_only = true;
+    }
+
+    /// Returns where the cursor was when the document was last left, so a view showing it again
+    /// can start there
+    pub fn get_last_cursor(&self) -> Coords {
+        self.last_cursor
+    }
+
+    pub fn set_last_cursor(&mut self, cursor: Coords) {
+        self.last_cursor = cursor;
+    }
+
+    pub fn get_tab_stop(&self) -> usize {
+        self.tab_stop
+    }
+
+    pub fn set_tab_stop(&mut self, tab_stop: usize) {
+        self.tab_stop = tab_stop;
+    }
+
+    /// Returns the notable facts about the document worth telling the user, such as `[binary]`
+    pub fn get_flags(&self) -> Vec<String> {
+        let mut flags = vec![];
+
+        if self.new_file {
+            flags.push("[New File]".to_owned());
+        }
+
+        if self.binary {
+            flags.push("[binary]".to_owned());
+        } else if self.format.encoding != encoding_rs::UTF_8 {
+            flags.push(format!("[{}]", encoding::get_name(self.format.encoding)));
+        }
+
+        if self.read_only {
+            flags.push("[readonly]".to_owned());
+        }
+
+        flags
+    }
+
+    /// Changes the encoding used when writing the document, which counts as a modification
+    pub fn set_encoding(&mut self, encoding: &'static encoding_rs::Encoding) {
+        if self.format.encoding != encoding {
+            self.format.encoding = encoding;
+            self.format_changed = true;
+            self.dirty = true;
+        }
+    }
+
+    /// Changes the line ending used when writing the document, which counts as a modification
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.format.line_ending != line_ending {
+            self.format.line_ending = line_ending;
+            self.format_changed = true;
+            self.dirty = true;
+        }
+    }
+
+    /// Serializes the content as it would be written to disk
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        self.format.apply(&self.content)
+    }
+
+    /// Prepares writing the content to `file_path`, or to the document's own path when `None`.
+    /// With `rename`, the document moves to `file_path` once the write succeeded, like with
+    /// `:saveas`.
+    ///
+    /// The write itself happens in [`WriteJob::run`], which doesn't borrow the document, so it
+    /// can be awaited without keeping a shared document borrowed. Call [`Document::mark_written`]
+    /// with the result once it is done.
+    pub fn prepare_write(
+        &self,
+        file_path: Option<&str>,
+        create_dirs: bool,
+        rename: bool,
+    ) -> anyhow::Result<WriteJob> {
+        let file_path = file_path
+            .or(self.file_path.as_deref())
+            .ok_or(anyhow::anyhow!("No file name"))?
+            .to_owned();
+
+        let is_own_path =
+            rename || self.file_path.is_none() || self.file_path.as_deref() == Some(&file_path);
+
+        if self.read_only && is_own_path {
+            anyhow::bail!("File is read-only");
+        }
+
+        Ok(WriteJob {
+            history: is_own_path.then(|| self.history.clone()),
+            file_path,
+            bytes: self.to_bytes()?,
+            lines: self.content.len_lines(),
+            create_dirs,
+            rename,
+        })
+    }
+
+    /// Updates the document after a successful write. Writing a document without a path or
+    /// renaming it binds it to the written path, and writing to its own path clears the dirty
+    /// flag.
+    pub fn mark_written(&mut self, stats: &WriteStats) {
+        if self.file_path.is_none() || stats.rename {
+            self.file_path = Some(stats.file_path.clone());
+        }
+
+        if self.file_path.as_deref() == Some(stats.file_path.as_str()) {
+            self.history.commit();
+            self.saved_node = self.history.get_current();
+            self.format_changed = false;
+            self.dirty = false;
+            self.new_file = false;
+        }
+    }
+
+    /// Inserts `text` at `at`, recording it in the history. `cursor` is where the cursor goes
+    /// back to when the change is undone.
+    pub fn insert(&mut self, at: Coords, text: &str, cursor: Coords) -> anyhow::Result<()> {
+        self.check_writable()?;
+        self.content.insert(at, text)?;
+
+        self.history.record(
+            Edit::Insert {
+                at,
+                text: text.to_owned(),
+            },
+            cursor,
+        );
+        self.dirty = true;
+
+        Ok(())
+    }
+
+    /// Removes `len` chars from `at`, recording it in the history. `cursor` is where the cursor
+    /// goes back to when the change is undone.
+    pub fn remove(&mut self, at: Coords, len: usize, cursor: Coords) -> anyhow::Result<String> {
+        self.check_writable()?;
+        let text = self.content.remove(at, len)?;
+
+        self.history.record(
+            Edit::Remove {
+                at,
+                text: text.clone(),
+            },
+            cursor,
+        );
+        self.dirty = true;
+
+        Ok(text)
+    }
+
+    /// Undoes the last change, returning where the cursor should go
+    pub fn undo(&mut self) -> anyhow::Result<Option<Coords>> {
+        let revision = self.history.undo();
+
+        self.apply_revision(revision)
+    }
+
+    /// Redoes the last undone change, returning where the cursor should go
+    pub fn redo(&mut self) -> anyhow::Result<Option<Coords>> {
+        let revision = self.history.redo();
+
+        self.apply_revision(revision)
+    }
+
+    /// Moves through the history in chronological order, returning where the cursor should go
+    pub fn travel(&mut self, steps: isize) -> anyhow::Result<Option<Coords>> {
+        let revision = self.history.travel(steps);
+
+        self.apply_revision(revision)
+    }
+
+    fn check_writable(&self) -> anyhow::Result<()> {
+        if self.read_only {
+            anyhow::bail!("Cannot modify a read-only document");
+        }
+
+        Ok(())
+    }
+
+    fn apply_revision(&mut self, revision: Option<Revision>) -> anyhow::Result<Option<Coords>> {
+        let Some(revision) = revision else {
+            return Ok(None);
+        };
+
+        for edit in revision.edits.iter() {
+            match edit {
+                Edit::Insert { at, text } => self.content.insert(*at, text)?,
+                Edit::Remove { at, text } => {
+                    self.content.remove(*at, text.chars().count())?;
+                }
+            };
+        }
+
+        self.dirty = self.format_changed || self.history.get_current() != self.saved_node;
+
+        Ok(Some(revision.cursor))
+    }
+}
+
+/// A pending write of a document's content, see [`Document::prepare_write`]
+pub struct WriteJob {
+    file_path: String,
+    bytes: Vec<u8>,
+    lines: usize,
+    history: Option<History>,
+    create_dirs: bool,
+    rename: bool,
+}
+
+impl WriteJob {
+    /// Writes the content to a temporary file next to the target first and then renames it over
+    /// the target, so a failed write never leaves a truncated file behind. When the target
+    /// already exists, its permissions are carried over to the new file. Writing to the
+    /// document's own path also persists the undo history, so it can be restored the next time
+    /// the file is opened.
+    ///
+    /// Missing parent directories are only created when `create_dirs` was requested.
+    pub async fn run(self) -> anyhow::Result<WriteStats> {
+        let path = Path::new(&self.file_path);
+
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            if self.create_dirs {
+                tokio::fs::create_dir_all(parent).await?;
+            } else if !tokio::fs::try_exists(parent).await? {
+                anyhow::bail!(
+                    "Directory \"{}\" does not exist (add ++p to create it)",
+                    parent.display()
+                );
+            }
+        }
+
+        write_atomic(path, &self.bytes).await?;
+
+        if let Some(history) = &self.history {
+            // The history is a cache, failing to persist it shouldn't fail the write
+            let hash = undo_file::hash_content(&self.bytes);
+            let _ = undo_file::save(path, hash, history).await;
+        }
+
+        Ok(WriteStats {
+            file_path: self.file_path,
+            rename: self.rename,
+            lines: self.lines,
+            bytes: self.bytes.len(),
+        })
+    }
+}
+
+/// Formats bytes like `xxd` does, 16 per line with their offset and their ASCII representation
+fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex = chunk
+                .chunks(2)
+                .map(|pair| {
+                    pair.iter()
+                        .map(|b| format!("{:02x}", b))
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = chunk
+                .iter()
+                .map(|&b| match b.is_ascii_graphic() || b == b' ' {
+                    true => b as char,
+                    false => '.',
+                })
+                .collect::<String>();
+
+            format!("{:08x}: {:<39}  {}", i * 16, hex, ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Writes `bytes` to a temporary file next to `path` and renames it over `path`, so readers never
+/// see a partly written file
+pub(crate) async fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    // Write through symlinks instead of replacing them
+    let path = match tokio::fs::canonicalize(path).await {
+        Ok(path) => path,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(e),
+    };
+
+    let permissions = match tokio::fs::metadata(&path).await {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    let temp_path = get_temp_path(&path);
+
+    let result = async {
+        tokio::fs::write(&temp_path, bytes).await?;
+
+        if let Some(permissions) = permissions {
+            tokio::fs::set_permissions(&temp_path, permissions).await?;
+        }
+
+        tokio::fs::rename(&temp_path, &path).await
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+
+    result
+}
+
+fn get_temp_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(".{}.{}.chai-tmp", file_name, std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    async fn open(file_path: &str) -> Document {
+        Document::new(Some(file_path.to_owned())).await.unwrap()
+    }
+
+    /// Writes `document` like the write commands do, to its own path when `file_path` is `None`
+    async fn write(
+        document: &mut Document,
+        file_path: Option<&str>,
+        rename: bool,
+    ) -> anyhow::Result<()> {
+        let stats = document
+            .prepare_write(file_path, false, rename)?
+            .run()
+            .await?;
+
+        document.mark_written(&stats);
+
+        Ok(())
+    }
+
+    fn edit(document: &mut Document) {
+        document.insert((0, 0).into(), "x", (0, 0).into()).unwrap();
+        document.commit();
+    }
+
+    /// Opens a file holding `bytes`, checking that writing it back without edits gives the same
+    /// bytes
+    async fn round_trip(dir: &TempDir, bytes: &[u8]) -> (String, Document) {
+        let file_path = dir.get_path().join("file").to_string_lossy().into_owned();
+
+        std::fs::write(&file_path, bytes).unwrap();
+
+        let mut document = open(&file_path).await;
+
+        write(&mut document, None, false).await.unwrap();
+
+        assert_eq!(std::fs::read(&file_path).unwrap(), bytes);
+
+        (file_path, document)
+    }
+
+    #[tokio::test]
+    async fn line_endings_and_bom_survive_a_write() {
+        let dir = TempDir::new("document-line-endings");
+
+        let (file_path, mut document) = round_trip(&dir, b"a\r\nb").await;
+
+        assert_eq!(document.get_content().to_string(), "a\nb");
+        assert!(document.get_format().line_ending == LineEnding::Dos);
+        assert!(!document.get_format().trailing_newline);
+
+        edit(&mut document);
+        write(&mut document, None, false).await.unwrap();
+
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"xa\r\nb");
+
+        let (file_path, mut document) = round_trip(&dir, b"\xef\xbb\xbfa\n").await;
+
+        assert_eq!(document.get_content().to_string(), "a");
+        assert!(document.get_format().bom);
+
+        edit(&mut document);
+        write(&mut document, None, false).await.unwrap();
+
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"\xef\xbb\xbfxa\n");
+    }
+
+    #[tokio::test]
+    async fn latin1_files_are_written_back_in_latin1() {
+        let dir = TempDir::new("document-latin1");
+
+        let (file_path, mut document) = round_trip(&dir, b"caf\xe9\n").await;
+
+        assert_eq!(document.get_content().to_string(), "café");
+        assert_eq!(document.get_flags(), ["[latin1]"]);
+
+        document
+            .insert((4, 0).into(), "\u{e8}", (4, 0).into())
+            .unwrap();
+        write(&mut document, None, false).await.unwrap();
+
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"caf\xe9\xe8\n");
+    }
+
+    #[tokio::test]
+    async fn binary_files_open_as_a_read_only_hex_dump() {
+        let dir = TempDir::new("document-binary");
+        let file_path = dir.get_path().join("file.bin");
+
+        std::fs::write(&file_path, b"a\x00b").unwrap();
+
+        let mut document = open(file_path.to_str().unwrap()).await;
+
+        assert_eq!(
+            document.get_content().to_string(),
+            format!("00000000: 6100 62{}a.b", " ".repeat(34))
+        );
+        assert!(document.is_read_only());
+        assert_eq!(document.get_flags(), ["[binary]", "[readonly]"]);
+        assert!(document.insert((0, 0).into(), "x", (0, 0).into()).is_err());
+        assert!(document.prepare_write(None, false, false).is_err());
+    }
+
+    #[tokio::test]
+    async fn writing_saves_the_content_and_clears_the_dirty_flag() {
+        let dir = TempDir::new("document-write");
+        let file_path = dir.create_file("a.txt", "text\n");
+        let mut document = open(&file_path).await;
+
+        edit(&mut document);
+        write(&mut document, None, false).await.unwrap();
+
+        assert!(!document.is_dirty());
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "xtext\n");
+    }
+
+    #[tokio::test]
+    async fn only_renaming_moves_the_document_to_the_written_path() {
+        let dir = TempDir::new("document-rename");
+        let file_path = dir.create_file("a.txt", "text\n");
+        let other_path = dir.get_path().join("b.txt").to_string_lossy().into_owned();
+        let mut document = open(&file_path).await;
+
+        edit(&mut document);
+        write(&mut document, Some(&other_path), false)
+            .await
+            .unwrap();
+
+        assert_eq!(document.get_file_path(), Some(file_path.as_str()));
+        assert!(document.is_dirty());
+        assert_eq!(std::fs::read_to_string(&other_path).unwrap(), "xtext\n");
+
+        write(&mut document, Some(&other_path), true).await.unwrap();
+
+        assert_eq!(document.get_file_path(), Some(other_path.as_str()));
+        assert!(!document.is_dirty());
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "text\n");
+    }
+
+    #[tokio::test]
+    async fn writing_keeps_the_permissions_of_the_file() {
+        let dir = TempDir::new("document-permissions");
+        let file_path = dir.create_file("script.sh", "echo\n");
+
+        std::fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o751)).unwrap();
+
+        let mut document = open(&file_path).await;
+
+        edit(&mut document);
+        write(&mut document, None, false).await.unwrap();
+
+        let mode = std::fs::metadata(&file_path).unwrap().permissions().mode();
+
+        assert_eq!(mode & 0o777, 0o751);
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "xecho\n");
+    }
+
+    #[tokio::test]
+    async fn failed_writes_leave_no_temporary_file() {
+        let dir = TempDir::new("document-failed-write");
+        let file_path = dir.get_path().join("a.txt");
+
+        // Renaming the temporary file over a directory fails
+        std::fs::create_dir(&file_path).unwrap();
+
+        let mut document = Document::from("text");
+        let result = write(&mut document, file_path.to_str(), false).await;
+
+        assert!(result.is_err());
+        assert!(document.get_file_path().is_none());
+
+        let entries = std::fs::read_dir(dir.get_path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+
+        assert_eq!(entries, ["a.txt"]);
+    }
 }
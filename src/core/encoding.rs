@@ -0,0 +1,197 @@
+use std::io;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+/// Text decoded from a file, along with what is needed to encode it back
+pub struct Decoded {
+    pub text: String,
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+}
+
+/// Decodes the content of a file, returning `None` when it looks like a binary file.
+///
+/// A BOM decides the encoding when present, and content that isn't valid in that encoding is an
+/// error. Otherwise UTF-16 is recognized by the zero bytes of ASCII characters, and anything that
+/// is neither UTF-8 nor binary is read as Latin-1.
+pub fn decode(bytes: &[u8]) -> io::Result<Option<Decoded>> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (text, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+
+        if had_errors {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Cannot decode the file as {}", get_name(encoding)),
+            ));
+        }
+
+        return Ok(Some(Decoded {
+            text: text.into_owned(),
+            encoding,
+            bom: true,
+        }));
+    }
+
+    if let Some(encoding) = guess_utf_16(bytes) {
+        let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+
+        if !had_errors {
+            return Ok(Some(Decoded {
+                text: text.into_owned(),
+                encoding,
+                bom: false,
+            }));
+        }
+    }
+
+    if is_binary(bytes) {
+        return Ok(None);
+    }
+
+    let encoding = match std::str::from_utf8(bytes) {
+        Ok(_) => UTF_8,
+        Err(_) => WINDOWS_1252,
+    };
+
+    let (text, _) = encoding.decode_without_bom_handling(bytes);
+
+    Ok(Some(Decoded {
+        text: text.into_owned(),
+        encoding,
+        bom: false,
+    }))
+}
+
+/// Encodes `text` back to `encoding`, failing when it contains characters the encoding can't
+/// represent
+pub fn encode(text: &str, encoding: &'static Encoding, bom: bool) -> anyhow::Result<Vec<u8>> {
+    // encoding_rs only decodes UTF-16, so it is encoded by hand
+    let mut bytes = match encoding {
+        e if e == UTF_16LE => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+        e if e == UTF_16BE => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        _ => {
+            let (bytes, _, had_errors) = encoding.encode(text);
+
+            if had_errors {
+                anyhow::bail!("Cannot convert the text to {}", get_name(encoding));
+            }
+
+            bytes.into_owned()
+        }
+    };
+
+    if bom {
+        let bom_bytes: &[u8] = match encoding {
+            e if e == UTF_8 => &[0xef, 0xbb, 0xbf],
+            e if e == UTF_16LE => &[0xff, 0xfe],
+            e if e == UTF_16BE => &[0xfe, 0xff],
+            _ => &[],
+        };
+
+        bytes.splice(0..0, bom_bytes.iter().copied());
+    }
+
+    Ok(bytes)
+}
+
+/// Finds an encoding by any of its usual names, such as `latin1`, `utf-8` or `utf-16le`
+pub fn find_encoding(name: &str) -> Option<&'static Encoding> {
+    match name.to_ascii_lowercase().as_str() {
+        "utf-16" => Some(UTF_16LE),
+        name => Encoding::for_label(name.as_bytes()),
+    }
+}
+
+/// Returns the name of `encoding` as shown to the user
+pub fn get_name(encoding: &'static Encoding) -> String {
+    match encoding {
+        e if e == WINDOWS_1252 => "latin1".to_owned(),
+        e => e.name().to_ascii_lowercase(),
+    }
+}
+
+/// Guesses UTF-16 without a BOM when most of the even or odd bytes are zeroes, which is what
+/// mostly ASCII text looks like in UTF-16
+fn guess_utf_16(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+
+    // A NUL character is a sign of a binary file rather than UTF-16 text
+    if bytes.chunks_exact(2).any(|pair| pair == [0, 0]) {
+        return None;
+    }
+
+    let pairs = bytes.len() / 2;
+    let (even_zeroes, odd_zeroes) = bytes.chunks_exact(2).fold((0, 0), |(even, odd), pair| {
+        (
+            even + (pair[0] == 0) as usize,
+            odd + (pair[1] == 0) as usize,
+        )
+    });
+
+    match (even_zeroes * 10 >= pairs * 7, odd_zeroes * 10 >= pairs * 7) {
+        (false, true) => Some(UTF_16LE),
+        (true, false) => Some(UTF_16BE),
+        _ => None,
+    }
+}
+
+/// Looks for zero bytes or a high proportion of control characters in the first few kilobytes
+fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(8192)];
+
+    let control_chars = sample
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+
+    sample.contains(&0) || control_chars * 10 > sample.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoded_text_encodes_back_to_the_same_bytes() {
+        for (bytes, encoding, bom) in [
+            ("añb\n".as_bytes(), UTF_8, false),
+            (b"\xef\xbb\xbfa\xc3\xb1b\n", UTF_8, true),
+            (b"\xff\xfea\x00\xf1\x00b\x00\n\x00", UTF_16LE, true),
+            (b"\xfe\xff\x00a\x00\xf1\x00b\x00\n", UTF_16BE, true),
+            (b"a\x00\xf1\x00b\x00\n\x00", UTF_16LE, false),
+            (b"a\xf1b\n", WINDOWS_1252, false),
+        ] {
+            let decoded = decode(bytes).unwrap().unwrap();
+
+            assert_eq!(decoded.text, "añb\n");
+            assert_eq!(decoded.encoding, encoding);
+            assert_eq!(decoded.bom, bom);
+            assert_eq!(encode(&decoded.text, encoding, bom).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn binary_content_is_not_decoded() {
+        assert!(decode(b"\x7fELF\x02\x01\x01\x00\x00\x00")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_content_after_a_bom_is_an_error() {
+        let error = decode(b"\xef\xbb\xbfa\xff\x00\x01").err().unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "Cannot decode the file as utf-8");
+
+        // A lone trailing byte can't be UTF-16
+        assert!(decode(b"\xff\xfea\x00b").is_err());
+    }
+
+    #[test]
+    fn unrepresentable_characters_fail_to_encode() {
+        assert!(encode("中", WINDOWS_1252, false).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use std::{fmt::Display, io, str::FromStr};
+
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::core::{encoding, Buffer};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Unix,
+    Dos,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Unix => "\n",
+            LineEnding::Dos => "\r\n",
+        }
+    }
+}
+
+impl Display for LineEnding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineEnding::Unix => write!(f, "unix"),
+            LineEnding::Dos => write!(f, "dos"),
+        }
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unix" => Ok(LineEnding::Unix),
+            "dos" => Ok(LineEnding::Dos),
+            _ => Err(anyhow::anyhow!("Invalid file format: {}", s)),
+        }
+    }
+}
+
+/// How the text of a document is laid out on disk. The buffer always holds UTF-8 text with `\n`
+/// line breaks and never contains the final line break or the BOM, so these are kept here to
+/// write the file back byte for byte.
+#[derive(Clone, Copy)]
+pub struct FileFormat {
+    pub line_ending: LineEnding,
+    pub trailing_newline: bool,
+    pub bom: bool,
+    pub encoding: &'static Encoding,
+}
+
+impl Default for FileFormat {
+    fn default() -> Self {
+        Self {
+            line_ending: LineEnding::Unix,
+            trailing_newline: true,
+            bom: false,
+            encoding: UTF_8,
+        }
+    }
+}
+
+impl FileFormat {
+    /// Detects the format of a file, returning it along with the text as it should be stored in
+    /// the buffer, or `None` when the file looks binary. A file that starts with a BOM but can't
+    /// be decoded in its encoding is an error.
+    ///
+    /// Like vim, a file is only considered `dos` when every line ends in `\r\n`. Otherwise the
+    /// `\r` are kept as part of the lines, so files with mixed line endings aren't altered.
+    pub fn detect(bytes: &[u8]) -> io::Result<Option<(FileFormat, String)>> {
+        let Some(decoded) = encoding::decode(bytes)? else {
+            return Ok(None);
+        };
+        let text = decoded.text.as_str();
+
+        let line_breaks = text.matches('\n').count();
+        let dos_line_breaks = text.matches("\r\n").count();
+
+        let line_ending = match line_breaks > 0 && line_breaks == dos_line_breaks {
+            true => LineEnding::Dos,
+            false => LineEnding::Unix,
+        };
+
+        let (trailing_newline, text) = match text.strip_suffix(line_ending.as_str()) {
+            Some(text) => (true, text),
+            None => (false, text),
+        };
+
+        let text = match line_ending {
+            LineEnding::Unix => text.to_owned(),
+            LineEnding::Dos => text.replace("\r\n", "\n"),
+        };
+
+        let format = FileFormat {
+            line_ending,
+            trailing_newline,
+            bom: decoded.bom,
+            encoding: decoded.encoding,
+        };
+
+        Ok(Some((format, text)))
+    }
+
+    /// Serializes `content` back to the bytes that should be written to disk
+    pub fn apply(&self, content: &Buffer) -> anyhow::Result<Vec<u8>> {
+        let mut text = String::new();
+
+        for chunk in content.chunks() {
+            match self.line_ending {
+                LineEnding::Unix => text.push_str(chunk),
+                LineEnding::Dos => text.push_str(&chunk.replace('\n', "\r\n")),
+            }
+        }
+
+        if self.trailing_newline {
+            text.push_str(self.line_ending.as_str());
+        }
+
+        encoding::encode(&text, self.encoding, self.bom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Detects the format of `bytes`, checking that writing the text back gives the same bytes
+    fn detect(bytes: &[u8]) -> (FileFormat, String) {
+        let (format, text) = FileFormat::detect(bytes).unwrap().unwrap();
+
+        assert_eq!(format.apply(&Buffer::from(text.as_str())).unwrap(), bytes);
+
+        (format, text)
+    }
+
+    #[test]
+    fn line_endings_are_detected() {
+        for (bytes, line_ending, text) in [
+            (&b"a\nb\n"[..], LineEnding::Unix, "a\nb"),
+            (b"a\r\nb\r\n", LineEnding::Dos, "a\nb"),
+            // With mixed line endings the `\r` are kept in the text
+            (b"a\r\nb\n", LineEnding::Unix, "a\r\nb"),
+            (b"a\nb\r\n", LineEnding::Unix, "a\nb\r"),
+            (b"", LineEnding::Unix, ""),
+        ] {
+            let (format, detected) = detect(bytes);
+
+            assert!(format.line_ending == line_ending, "{:?}", bytes);
+            assert_eq!(detected, text);
+        }
+    }
+
+    #[test]
+    fn missing_final_newline_is_kept() {
+        let (format, text) = detect(b"a\nb");
+
+        assert!(!format.trailing_newline);
+        assert_eq!(text, "a\nb");
+
+        let (format, text) = detect(b"a\r\nb");
+
+        assert!(format.line_ending == LineEnding::Dos);
+        assert!(!format.trailing_newline);
+        assert_eq!(text, "a\nb");
+
+        let (format, _) = detect(b"a\n");
+
+        assert!(format.trailing_newline);
+    }
+
+    #[test]
+    fn utf8_bom_is_kept() {
+        let (format, text) = detect(b"\xef\xbb\xbfa\r\n");
+
+        assert!(format.bom);
+        assert!(format.line_ending == LineEnding::Dos);
+        assert_eq!(text, "a");
+    }
+}
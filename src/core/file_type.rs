@@ -0,0 +1,33 @@
+use std::path::Path;
+
+/// File types by the extensions or file names they are recognized by
+const FILE_TYPES: &[(&str, &[&str])] = &[
+    ("rust", &["rs"]),
+    ("toml", &["toml"]),
+    ("markdown", &["md", "markdown"]),
+    ("json", &["json"]),
+    ("yaml", &["yaml", "yml"]),
+    ("c", &["c", "h"]),
+    ("cpp", &["cpp", "cc", "cxx", "hpp", "hh"]),
+    ("go", &["go"]),
+    ("python", &["py"]),
+    ("javascript", &["js", "mjs", "cjs"]),
+    ("typescript", &["ts", "tsx"]),
+    ("html", &["html", "htm"]),
+    ("css", &["css"]),
+    ("sh", &["sh", "bash", "zsh"]),
+    ("make", &["Makefile", "makefile", "mk"]),
+    ("text", &["txt"]),
+];
+
+/// Detects the file type of `file_path` from its extension or, for files like `Makefile`, from
+/// its name
+pub fn detect(file_path: &str) -> Option<&'static str> {
+    let path = Path::new(file_path);
+    let key = path.extension().or(path.file_name())?.to_str()?;
+
+    FILE_TYPES
+        .iter()
+        .find(|(_, keys)| keys.contains(&key))
+        .map(|(file_type, _)| *file_type)
+}
@@ -0,0 +1,345 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::Coords;
+
+/// A single reversible edit. `text` may span multiple lines, using `\n` as the separator.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Edit {
+    Insert { at: Coords, text: String },
+    Remove { at: Coords, text: String },
+}
+
+impl Edit {
+    pub fn invert(&self) -> Edit {
+        match self {
+            Edit::Insert { at, text } => Edit::Remove {
+                at: *at,
+                text: text.clone(),
+            },
+            Edit::Remove { at, text } => Edit::Insert {
+                at: *at,
+                text: text.clone(),
+            },
+        }
+    }
+}
+
+/// A group of edits undone and redone as a unit, along with the cursor position from before
+/// the first edit
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub edits: Vec<Edit>,
+    pub cursor: Coords,
+}
+
+/// The edits needed to move between two states of the undo tree, in the order they must be
+/// applied, and where the cursor should end up
+pub struct Revision {
+    pub edits: Vec<Edit>,
+    pub cursor: Coords,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Node {
+    parent: Option<usize>,
+    /// The child redo follows, which is the one most recently created or visited
+    last_child: Option<usize>,
+    change: Change,
+}
+
+/// An undo tree. Nodes are stored in creation order, so a node's index doubles as its sequence
+/// number for chronological (`g-`/`g+`) travel. The root (index 0) is the unmodified state.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct History {
+    nodes: Vec<Node>,
+    current: usize,
+    #[serde(skip)]
+    pending: Option<Change>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node {
+                parent: None,
+                last_child: None,
+                change: Change {
+                    edits: vec![],
+                    cursor: (0, 0).into(),
+                },
+            }],
+            current: 0,
+            pending: None,
+        }
+    }
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an edit to the change being built, starting a new one if needed
+    pub fn record(&mut self, edit: Edit, cursor: Coords) {
+        self.pending
+            .get_or_insert_with(|| Change {
+                edits: vec![],
+                cursor,
+            })
+            .edits
+            .push(edit);
+    }
+
+    /// Closes the change being built, adding it to the tree as a child of the current state
+    pub fn commit(&mut self) {
+        let Some(change) = self.pending.take() else {
+            return;
+        };
+
+        let index = self.nodes.len();
+
+        self.nodes.push(Node {
+            parent: Some(self.current),
+            last_child: None,
+            change,
+        });
+        self.nodes[self.current].last_child = Some(index);
+        self.current = index;
+    }
+
+    /// Returns the node of the current state, which identifies it across undos and redos
+    pub fn get_current(&self) -> usize {
+        self.current
+    }
+
+    pub fn undo(&mut self) -> Option<Revision> {
+        self.commit();
+
+        let node = &self.nodes[self.current];
+        let parent = node.parent?;
+
+        let revision = Revision {
+            edits: node.change.edits.iter().rev().map(Edit::invert).collect(),
+            cursor: node.change.cursor,
+        };
+
+        self.current = parent;
+
+        Some(revision)
+    }
+
+    pub fn redo(&mut self) -> Option<Revision> {
+        self.commit();
+
+        let child = self.nodes[self.current].last_child?;
+        let change = &self.nodes[child].change;
+
+        let revision = Revision {
+            edits: change.edits.clone(),
+            cursor: change.cursor,
+        };
+
+        self.current = child;
+
+        Some(revision)
+    }
+
+    /// Moves `steps` states back (negative) or forward (positive) in chronological order,
+    /// regardless of the branch they are on
+    pub fn travel(&mut self, steps: isize) -> Option<Revision> {
+        self.commit();
+
+        let target = self
+            .current
+            .saturating_add_signed(steps)
+            .min(self.nodes.len() - 1);
+
+        if target == self.current {
+            return None;
+        }
+
+        let ancestors = self.get_ancestors(target);
+
+        let mut edits = vec![];
+        let mut cursor = self.nodes[self.current].change.cursor;
+
+        // Undo up to the common ancestor...
+        while !ancestors.contains(&self.current) {
+            let revision = self.undo()?;
+
+            edits.extend(revision.edits);
+            cursor = revision.cursor;
+        }
+
+        // ...and redo down to the target
+        let ancestor_position = ancestors.iter().position(|&i| i == self.current)?;
+
+        for &index in ancestors[..ancestor_position].iter().rev() {
+            self.nodes[self.current].last_child = Some(index);
+
+            let revision = self.redo()?;
+
+            edits.extend(revision.edits);
+            cursor = revision.cursor;
+        }
+
+        Some(Revision { edits, cursor })
+    }
+
+    /// Checks that every index in the tree points to an existing node, so a corrupted history
+    /// loaded from disk can't cause out of bounds accesses
+    pub fn is_valid(&self) -> bool {
+        let len = self.nodes.len();
+
+        self.current < len
+            && self.nodes.iter().enumerate().all(|(i, node)| {
+                node.parent.map_or(i == 0, |parent| parent < i)
+                    && node.last_child.is_none_or(|child| child > i && child < len)
+            })
+    }
+
+    /// Returns the path from `index` up to the root, both included
+    fn get_ancestors(&self, index: usize) -> Vec<usize> {
+        let mut ancestors = vec![index];
+        let mut current = index;
+
+        while let Some(parent) = self.nodes[current].parent {
+            ancestors.push(parent);
+            current = parent;
+        }
+
+        ancestors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Buffer;
+
+    /// A buffer along with its history, edited like a document
+    struct Editor {
+        buffer: Buffer,
+        history: History,
+    }
+
+    impl Editor {
+        fn new() -> Self {
+            Self {
+                buffer: Buffer::from(""),
+                history: History::new(),
+            }
+        }
+
+        /// Appends `text` as a change of its own, made with the cursor at `cursor`
+        fn append(&mut self, text: &str, cursor: (usize, usize)) {
+            let at = (self.buffer.line_len(0).unwrap(), 0).into();
+
+            self.buffer.insert(at, text).unwrap();
+            self.history.record(
+                Edit::Insert {
+                    at,
+                    text: text.to_owned(),
+                },
+                cursor.into(),
+            );
+            self.history.commit();
+        }
+
+        /// Applies `revision`, returning the text and where the cursor goes
+        fn apply(&mut self, revision: Option<Revision>) -> Option<(String, (usize, usize))> {
+            let revision = revision?;
+
+            for edit in revision.edits {
+                match edit {
+                    Edit::Insert { at, text } => self.buffer.insert(at, &text).unwrap(),
+                    Edit::Remove { at, text } => {
+                        self.buffer.remove(at, text.chars().count()).unwrap();
+                    }
+                }
+            }
+
+            Some((
+                self.buffer.to_string(),
+                (revision.cursor.x, revision.cursor.y),
+            ))
+        }
+
+        fn undo(&mut self) -> Option<(String, (usize, usize))> {
+            let revision = self.history.undo();
+
+            self.apply(revision)
+        }
+
+        fn redo(&mut self) -> Option<(String, (usize, usize))> {
+            let revision = self.history.redo();
+
+            self.apply(revision)
+        }
+
+        fn travel(&mut self, steps: isize) -> Option<(String, (usize, usize))> {
+            let revision = self.history.travel(steps);
+
+            self.apply(revision)
+        }
+    }
+
+    /// Makes the tree `"" -> "a" -> "ab"`, then undoes `b` and types `c` on a new branch
+    fn branched() -> Editor {
+        let mut editor = Editor::new();
+
+        editor.append("a", (0, 0));
+        editor.append("b", (1, 0));
+        editor.undo();
+        editor.append("c", (1, 0));
+
+        editor
+    }
+
+    #[test]
+    fn edits_after_undo_start_a_branch() {
+        let editor = branched();
+
+        assert_eq!(editor.buffer.to_string(), "ac");
+        assert_eq!(editor.history.nodes.len(), 4);
+        assert_eq!(editor.history.nodes[3].parent, Some(1));
+        assert_eq!(editor.history.nodes[2].parent, Some(1));
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_cursor() {
+        let mut editor = branched();
+
+        assert_eq!(editor.undo(), Some(("a".to_owned(), (1, 0))));
+        assert_eq!(editor.undo(), Some(("".to_owned(), (0, 0))));
+        assert_eq!(editor.undo(), None);
+        assert_eq!(editor.redo(), Some(("a".to_owned(), (0, 0))));
+    }
+
+    #[test]
+    fn redo_follows_the_most_recent_branch() {
+        let mut editor = branched();
+
+        editor.undo();
+
+        assert_eq!(editor.redo(), Some(("ac".to_owned(), (1, 0))));
+        assert_eq!(editor.redo(), None);
+
+        // Visiting the older branch makes it the one redo follows
+        editor.travel(-1);
+        editor.undo();
+
+        assert_eq!(editor.redo(), Some(("ab".to_owned(), (1, 0))));
+    }
+
+    #[test]
+    fn travel_moves_chronologically_across_branches() {
+        let mut editor = branched();
+
+        assert_eq!(editor.travel(-1), Some(("ab".to_owned(), (1, 0))));
+        assert_eq!(editor.travel(-1), Some(("a".to_owned(), (1, 0))));
+        assert_eq!(editor.travel(2), Some(("ac".to_owned(), (1, 0))));
+        assert_eq!(editor.travel(1), None);
+        assert_eq!(editor.travel(-10), Some(("".to_owned(), (0, 0))));
+    }
+}
@@ -0,0 +1,302 @@
+use std::{borrow::Cow, ops::Range};
+
+use ropey::RopeSlice;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+pub const DEFAULT_TAB_STOP: usize = 8;
+
+/// The chars a line may be broken after with `linebreak` set, like vim's default `breakat`
+const BREAK_AT: &[&str] = &[
+    " ", "\t", "!", "@", "*", "-", "+", ";", ":", ",", ".", "/", "?",
+];
+
+/// Returns how `grapheme` is drawn at `column` and the number of columns it takes. Tabs are
+/// expanded to the next multiple of `tab_stop`, control chars are shown like `^[` as in vim, and
+/// zero width graphemes get a column of their own so the cursor can stop on them.
+pub fn get_symbol(grapheme: &str, column: usize, tab_stop: usize) -> (Cow<'_, str>, usize) {
+    let mut chars = grapheme.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some('\t'), None) => {
+            let width = tab_stop.max(1) - column % tab_stop.max(1);
+
+            (Cow::Owned(" ".repeat(width)), width)
+        }
+        (Some(c), None) if c.is_ascii_control() => {
+            (Cow::Owned(format!("^{}", (c as u8 ^ 0x40) as char)), 2)
+        }
+        _ => match grapheme.width() {
+            0 => (Cow::Owned(format!(" {}", grapheme)), 1),
+            width => (Cow::Borrowed(grapheme), width),
+        },
+    }
+}
+
+/// Where lines wrap, for views with `wrap` set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrapWidth {
+    /// The width of the first row of a line
+    pub width: usize,
+    /// The width of the rows a line continues on, which leave room for the `showbreak` marker
+    pub continuation_width: usize,
+    /// Wraps at the last char of `BREAK_AT` that fits rather than in the middle of a word
+    pub line_break: bool,
+}
+
+/// A grapheme cluster of a line along with where it is shown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grapheme {
+    pub chars: Range<usize>,
+    pub row: usize,
+    /// The first column, counted from the start of the row
+    pub column: usize,
+    pub width: usize,
+    pub symbol: String,
+}
+
+/// The display columns and screen rows of a line, which map char positions to where they are
+/// shown and back. Cursor positions stop at the start of each grapheme cluster.
+pub struct LineLayout {
+    graphemes: Vec<Grapheme>,
+    /// The char range shown on each row, of which there is at least one
+    rows: Vec<Range<usize>>,
+}
+
+impl LineLayout {
+    pub fn new(line: RopeSlice, tab_stop: usize, wrap: Option<WrapWidth>) -> Self {
+        let text = line.to_string();
+        let clusters = text
+            .graphemes(true)
+            .scan(0, |start, grapheme| {
+                let len = grapheme.chars().count();
+                *start += len;
+
+                Some((*start - len..*start, grapheme))
+            })
+            .collect::<Vec<_>>();
+
+        let mut graphemes: Vec<Grapheme> = vec![];
+        let mut rows = vec![];
+        let mut index = 0;
+
+        loop {
+            let row = rows.len();
+            let row_width = match (wrap, row) {
+                (None, _) => usize::MAX,
+                (Some(wrap), 0) => wrap.width.max(1),
+                (Some(wrap), _) => wrap.continuation_width.max(1),
+            };
+
+            let row_start = index;
+            let mut column = 0;
+            let mut last_break = None;
+
+            while let Some((chars, grapheme)) = clusters.get(index) {
+                let (symbol, mut width) = get_symbol(grapheme, column, tab_stop);
+
+                if column + width > row_width && column > 0 {
+                    // Tabs are cut at the end of the row, other graphemes move to the next one
+                    match *grapheme == "\t" && column < row_width {
+                        true => width = row_width - column,
+                        false => break,
+                    }
+                }
+
+                graphemes.push(Grapheme {
+                    chars: chars.clone(),
+                    row,
+                    column,
+                    width,
+                    symbol: match *grapheme == "\t" {
+                        true => " ".repeat(width),
+                        false => symbol.into_owned(),
+                    },
+                });
+
+                column += width;
+                index += 1;
+
+                if BREAK_AT.contains(grapheme) {
+                    last_break = Some(index);
+                }
+            }
+
+            let line_break = wrap.is_some_and(|wrap| wrap.line_break);
+
+            if let Some(last_break) = last_break.filter(|_| line_break && index < clusters.len()) {
+                graphemes.truncate(last_break);
+                index = last_break;
+            }
+
+            let start = clusters.get(row_start).map_or(0, |(chars, _)| chars.start);
+            let end = clusters
+                .get(index)
+                .map_or(line.len_chars(), |(chars, _)| chars.start);
+
+            rows.push(start..end);
+
+            if index >= clusters.len() {
+                return Self { graphemes, rows };
+            }
+        }
+    }
+
+    pub fn get_graphemes(&self) -> &[Grapheme] {
+        &self.graphemes
+    }
+
+    pub fn len_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns the number of columns taken by `row`
+    pub fn get_row_width(&self, row: usize) -> usize {
+        self.graphemes
+            .iter()
+            .rfind(|grapheme| grapheme.row == row)
+            .map_or(0, |grapheme| grapheme.column + grapheme.width)
+    }
+
+    /// Returns the row and column the char at `x` is shown at. Positions past the end of the
+    /// line are right after the last grapheme.
+    pub fn get_position(&self, x: usize) -> (usize, usize) {
+        match self.get_grapheme(x) {
+            Some(grapheme) => (grapheme.row, grapheme.column),
+            None => {
+                let row = self.rows.len() - 1;
+
+                (row, self.get_row_width(row))
+            }
+        }
+    }
+
+    /// Returns the width of the grapheme at `x`, which is 0 past the end of the line
+    pub fn get_width(&self, x: usize) -> usize {
+        self.get_grapheme(x).map_or(0, |grapheme| grapheme.width)
+    }
+
+    /// Returns the position of the grapheme shown at `column` of `row`, or of the last one of
+    /// the row when it isn't as wide
+    pub fn get_x(&self, row: usize, column: usize) -> usize {
+        self.graphemes
+            .iter()
+            .rfind(|grapheme| grapheme.row == row && grapheme.column <= column)
+            .map_or(self.rows[row.min(self.rows.len() - 1)].start, |grapheme| {
+                grapheme.chars.start
+            })
+    }
+
+    /// Returns the position of the grapheme after the one at `x`, or the end of the line
+    pub fn get_next_x(&self, x: usize) -> usize {
+        self.graphemes
+            .iter()
+            .find(|grapheme| grapheme.chars.start > x)
+            .map_or(self.get_len(), |grapheme| grapheme.chars.start)
+    }
+
+    /// Returns the position of the grapheme before the one at `x`
+    pub fn get_prev_x(&self, x: usize) -> usize {
+        self.graphemes
+            .iter()
+            .rfind(|grapheme| grapheme.chars.end <= x)
+            .map_or(0, |grapheme| grapheme.chars.start)
+    }
+
+    fn get_grapheme(&self, x: usize) -> Option<&Grapheme> {
+        self.graphemes
+            .iter()
+            .find(|grapheme| grapheme.chars.contains(&x))
+    }
+
+    fn get_len(&self) -> usize {
+        self.rows.last().map_or(0, |row| row.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use super::*;
+
+    fn layout(text: &str, wrap: Option<WrapWidth>) -> LineLayout {
+        LineLayout::new(Rope::from_str(text).slice(..), 4, wrap)
+    }
+
+    fn wrap(text: &str, width: usize, continuation_width: usize, line_break: bool) -> Vec<String> {
+        let rope = Rope::from_str(text);
+        let layout = LineLayout::new(
+            rope.slice(..),
+            4,
+            Some(WrapWidth {
+                width,
+                continuation_width,
+                line_break,
+            }),
+        );
+
+        layout
+            .rows
+            .into_iter()
+            .map(|row| rope.slice(row).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn tabs_expand_to_the_next_tab_stop() {
+        let layout = layout("a\tb\t\tc", None);
+
+        assert_eq!(layout.get_position(2), (0, 4));
+        assert_eq!(layout.get_position(3), (0, 5));
+        assert_eq!(layout.get_position(5), (0, 12));
+        assert_eq!(layout.get_row_width(0), 13);
+    }
+
+    #[test]
+    fn wide_chars_take_two_columns() {
+        let layout = layout("a中文b", None);
+
+        assert_eq!(layout.get_position(2), (0, 3));
+        assert_eq!(layout.get_position(3), (0, 5));
+        assert_eq!(layout.get_x(0, 2), 1);
+    }
+
+    #[test]
+    fn grapheme_clusters_are_single_stops() {
+        // An e with a combining acute accent and a family emoji joined by zero width joiners
+        let layout = layout("e\u{301}👨\u{200d}👩\u{200d}👧x", None);
+
+        assert_eq!(layout.get_next_x(0), 2);
+        assert_eq!(layout.get_next_x(2), 7);
+        assert_eq!(layout.get_prev_x(7), 2);
+        assert_eq!(layout.get_position(7), (0, 3));
+    }
+
+    #[test]
+    fn control_chars_are_shown_with_a_caret() {
+        let layout = layout("\u{1b}x", None);
+
+        assert_eq!(layout.get_graphemes()[0].symbol, "^[");
+        assert_eq!(layout.get_position(1), (0, 2));
+    }
+
+    #[test]
+    fn lines_are_cut_at_the_width() {
+        assert_eq!(wrap("abcdefgh", 3, 3, false), ["abc", "def", "gh"]);
+        assert_eq!(wrap("abcdef", 3, 3, false), ["abc", "def"]);
+        assert_eq!(wrap("", 3, 3, false), [""]);
+        assert_eq!(wrap("ab中", 3, 3, false), ["ab", "中"]);
+    }
+
+    #[test]
+    fn continuation_rows_leave_room_for_showbreak() {
+        assert_eq!(wrap("abcdefgh", 4, 2, false), ["abcd", "ef", "gh"]);
+    }
+
+    #[test]
+    fn line_break_keeps_words_whole() {
+        assert_eq!(wrap("one two three", 9, 9, true), ["one two ", "three"]);
+        assert_eq!(wrap("abcdefgh ij", 4, 4, true), ["abcd", "efgh", " ij"]);
+    }
+}
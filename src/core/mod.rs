@@ -1,7 +1,19 @@
+pub mod buffer;
+pub mod command;
 pub mod coords;
 pub mod document;
-pub mod extended_linked_list;
+pub mod encoding;
+pub mod file_format;
+pub mod file_type;
+pub mod history;
+pub mod layout;
+pub mod motion;
+pub mod normal_command;
+pub mod options;
+pub mod undo_file;
 
+pub use buffer::Buffer;
+pub use command::Command;
 pub use coords::{Coords, TermScreenCoords};
 pub use document::Document;
-pub use extended_linked_list::ExtendedLinkedList;
+pub use history::History;
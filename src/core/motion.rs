@@ -0,0 +1,921 @@
+//! Cursor motions, as functions from a position in a buffer to the position the cursor moves to,
+//! and text objects, as functions to the range of text they select. Positions past the end of a
+//! line are treated as being at its end.
+
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+use crate::core::{Buffer, Coords};
+
+/// The classes of chars word motions tell apart. Like in vim, a word is a run of chars of the
+/// same class other than blank, so `foo.bar` is three words and CJK text is split from latin text
+/// next to it. With `WORD`s, every char other than blank is in the same class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Blank,
+    Punctuation,
+    Word,
+    Emoji,
+    Ideograph,
+    Hiragana,
+    Katakana,
+    Hangul,
+}
+
+/// Returns the class of `c`, or `None` for the zero width chars joining the char before them,
+/// like combining accents, zero width joiners and variation selectors
+fn get_class(c: char, big_word: bool) -> Option<CharClass> {
+    if c.is_whitespace() {
+        return Some(CharClass::Blank);
+    }
+
+    if c.width() == Some(0) {
+        return None;
+    }
+
+    if big_word {
+        return Some(CharClass::Word);
+    }
+
+    let class = match c as u32 {
+        0x3040..=0x309f => CharClass::Hiragana,
+        0x30a0..=0x30ff => CharClass::Katakana,
+        0x1100..=0x11ff | 0x3130..=0x318f | 0xac00..=0xd7af => CharClass::Hangul,
+        0x3400..=0x4dbf | 0x4e00..=0x9fff | 0xf900..=0xfaff | 0x20000..=0x3134f => {
+            CharClass::Ideograph
+        }
+        0x2600..=0x27bf | 0x1f000..=0x1faff => CharClass::Emoji,
+        _ if c.is_alphanumeric() || c == '_' => CharClass::Word,
+        _ => CharClass::Punctuation,
+    };
+
+    Some(class)
+}
+
+/// Returns the absolute char index of `from`, moved back inside its line
+fn get_index(buffer: &Buffer, from: Coords) -> anyhow::Result<usize> {
+    let line_len = buffer
+        .line_len(from.y)
+        .ok_or(anyhow::anyhow!("No line at index {}", from.y))?;
+
+    buffer.char_index(Coords {
+        x: from.x.min(line_len),
+        y: from.y,
+    })
+}
+
+/// Returns the char ranges of the grapheme clusters of line `y`, which the cursor only stops at
+/// the start of
+fn get_graphemes(buffer: &Buffer, y: usize) -> anyhow::Result<Vec<Range<usize>>> {
+    let line = buffer
+        .line(y)
+        .ok_or(anyhow::anyhow!("No line at index {}", y))?;
+
+    let graphemes = line
+        .to_string()
+        .graphemes(true)
+        .scan(0, |start, grapheme| {
+            let len = grapheme.chars().count();
+            *start += len;
+
+            Some(*start - len..*start)
+        })
+        .collect();
+
+    Ok(graphemes)
+}
+
+/// `w` and `W`: the start of the next word. Empty lines count as words. Without a word after
+/// `from`, the cursor moves to the end of the buffer.
+pub fn next_word_start(buffer: &Buffer, from: Coords, big_word: bool) -> anyhow::Result<Coords> {
+    let mut index = get_index(buffer, from)?;
+    let mut chars = buffer.chars_at(index);
+
+    let Some(first) = chars.next() else {
+        return Ok(from);
+    };
+
+    // The current word is skipped first, then the blanks after it
+    let mut word = get_class(first, big_word).filter(|class| *class != CharClass::Blank);
+    let mut previous = first;
+
+    for c in chars {
+        index += 1;
+
+        match get_class(c, big_word) {
+            None => {}
+            Some(class) if word == Some(class) => {}
+            Some(CharClass::Blank) => {
+                word = None;
+
+                if c == '\n' && previous == '\n' {
+                    return Ok(buffer.coords(index));
+                }
+            }
+            Some(_) => return Ok(buffer.coords(index)),
+        }
+
+        previous = c;
+    }
+
+    // The buffer ends with an empty line after its last line break
+    match previous == '\n' {
+        true => Ok(buffer.coords(index + 1)),
+        false => Ok(buffer.coords(index)),
+    }
+}
+
+/// `e` and `E`: the end of the current word, or of the next one when already at its end
+pub fn next_word_end(buffer: &Buffer, from: Coords, big_word: bool) -> anyhow::Result<Coords> {
+    let mut index = get_index(buffer, from)?;
+    let mut chars = buffer.chars_at(index);
+    let mut word = None;
+    let mut end = None;
+
+    chars.next();
+
+    for c in chars {
+        index += 1;
+
+        match (word, get_class(c, big_word)) {
+            (_, None) | (None, Some(CharClass::Blank)) => {}
+            (None, Some(class)) => {
+                word = Some(class);
+                end = Some(index);
+            }
+            (Some(word), Some(class)) if word == class => end = Some(index),
+            (Some(_), Some(_)) => break,
+        }
+    }
+
+    Ok(end.map_or(from, |end| buffer.coords(end)))
+}
+
+/// `b` and `B`: the start of the current word, or of the previous one when already at its start.
+/// Empty lines count as words.
+pub fn prev_word_start(buffer: &Buffer, from: Coords, big_word: bool) -> anyhow::Result<Coords> {
+    let mut index = get_index(buffer, from)?;
+    let mut chars = buffer.chars_at(index);
+    let mut word = None;
+    let mut start = 0;
+
+    while let Some(c) = chars.prev() {
+        index -= 1;
+
+        match (word, get_class(c, big_word)) {
+            (_, None) => {}
+            (None, Some(CharClass::Blank)) if c == '\n' => {
+                // The line break of an empty line follows another line break
+                let empty_line = match chars.prev() {
+                    Some(previous) => {
+                        chars.next();
+
+                        previous == '\n'
+                    }
+                    None => true,
+                };
+
+                if empty_line {
+                    return Ok(buffer.coords(index));
+                }
+            }
+            (None, Some(CharClass::Blank)) => {}
+            (None, Some(class)) => {
+                word = Some(class);
+                start = index;
+            }
+            (Some(word), Some(class)) if word == class => start = index,
+            (Some(_), Some(_)) => break,
+        }
+    }
+
+    Ok(buffer.coords(start))
+}
+
+/// `0`: the start of the line
+pub fn line_start(from: Coords) -> Coords {
+    Coords { x: 0, y: from.y }
+}
+
+/// `^`: the first char of line `y` that isn't blank, or its end when it's blank
+pub fn first_non_blank(buffer: &Buffer, y: usize) -> anyhow::Result<Coords> {
+    let line = buffer
+        .line(y)
+        .ok_or(anyhow::anyhow!("No line at index {}", y))?;
+
+    let blanks = line.chars().take_while(|c| c.is_whitespace()).count();
+    let graphemes = get_graphemes(buffer, y)?;
+
+    // A mark combining with the last blank makes it the first char that isn't blank
+    let x = graphemes
+        .iter()
+        .find(|grapheme| grapheme.end > blanks)
+        .or(graphemes.last())
+        .map_or(0, |grapheme| grapheme.start);
+
+    Ok(Coords { x, y })
+}
+
+/// `$`: the last char of the line
+pub fn line_end(buffer: &Buffer, from: Coords) -> anyhow::Result<Coords> {
+    let x = get_graphemes(buffer, from.y)?
+        .last()
+        .map_or(0, |grapheme| grapheme.start);
+
+    Ok(Coords { x, y: from.y })
+}
+
+/// `gg`, `G` and `{N}G`: the first char that isn't blank of line `y`, or of the last line when
+/// there are fewer lines
+pub fn goto_line(buffer: &Buffer, y: usize) -> anyhow::Result<Coords> {
+    first_non_blank(buffer, y.min(buffer.len_lines() - 1))
+}
+
+/// A search for a char in the current line, made with `f`, `F`, `t` or `T`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharSearch {
+    pub c: char,
+    pub forward: bool,
+    /// Stops right before the char, or right after it when searching backwards
+    pub till: bool,
+}
+
+impl CharSearch {
+    /// The same search in the other direction, for `,`
+    pub fn reversed(self) -> Self {
+        Self {
+            forward: !self.forward,
+            ..self
+        }
+    }
+}
+
+/// `f`, `F`, `t` and `T`: the next occurrence of a char in the line, or `None` when there is no
+/// other one. When `repeated` with `;` or `,`, a `t` search right before the char goes on to the
+/// next one rather than staying in place.
+pub fn find_char(
+    buffer: &Buffer,
+    from: Coords,
+    search: CharSearch,
+    repeated: bool,
+) -> anyhow::Result<Option<Coords>> {
+    let line = buffer
+        .line(from.y)
+        .ok_or(anyhow::anyhow!("No line at index {}", from.y))?;
+
+    // The search goes by grapheme clusters, matching the char in any of them, so `t` and `T`
+    // stop on a whole cluster next to the one found
+    let graphemes = get_graphemes(buffer, from.y)?;
+    let cursor = graphemes
+        .iter()
+        .position(|grapheme| grapheme.end > from.x)
+        .unwrap_or(graphemes.len());
+
+    let skip = match search.till && repeated {
+        true => 2,
+        false => 1,
+    };
+
+    let matches = |index: &usize| {
+        line.slice(graphemes[*index].clone())
+            .chars()
+            .any(|c| c == search.c)
+    };
+
+    let found = match search.forward {
+        true => (cursor + skip..graphemes.len()).find(matches),
+        false => (0..cursor.saturating_sub(skip - 1)).rev().find(matches),
+    };
+
+    let index = match (found, search.till, search.forward) {
+        (None, _, _) => return Ok(None),
+        (Some(index), false, _) => index,
+        (Some(index), true, true) => index - 1,
+        (Some(index), true, false) => index + 1,
+    };
+
+    let x = graphemes
+        .get(index)
+        .map_or(line.len_chars(), |grapheme| grapheme.start);
+
+    Ok(Some(Coords { x, y: from.y }))
+}
+
+const BRACKETS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+/// `%`: the bracket matching the first one at or after the cursor in the line, or `None` when
+/// there is no bracket or it isn't matched
+pub fn matching_bracket(buffer: &Buffer, from: Coords) -> anyhow::Result<Option<Coords>> {
+    let line_len = buffer
+        .line_len(from.y)
+        .ok_or(anyhow::anyhow!("No line at index {}", from.y))?;
+
+    let is_bracket = |c: char| {
+        BRACKETS
+            .iter()
+            .any(|(open, close)| c == *open || c == *close)
+    };
+    let start = get_index(buffer, from)?;
+
+    let Some((mut index, bracket)) = buffer
+        .chars_at(start)
+        .take(line_len - from.x.min(line_len))
+        .enumerate()
+        .find(|(_, c)| is_bracket(*c))
+        .map(|(offset, c)| (start + offset, c))
+    else {
+        return Ok(None);
+    };
+
+    let (open, close) = BRACKETS
+        .iter()
+        .find(|(open, close)| bracket == *open || bracket == *close)
+        .ok_or(anyhow::anyhow!("Not a bracket: {}", bracket))?;
+
+    let mut depth = 0usize;
+
+    if bracket == *open {
+        for (offset, c) in buffer.chars_at(index + 1).enumerate() {
+            if c == *open {
+                depth += 1;
+            } else if c == *close {
+                if depth == 0 {
+                    return Ok(Some(buffer.coords(index + 1 + offset)));
+                }
+
+                depth -= 1;
+            }
+        }
+    } else {
+        let mut chars = buffer.chars_at(index);
+
+        while let Some(c) = chars.prev() {
+            index -= 1;
+
+            if c == *close {
+                depth += 1;
+            } else if c == *open {
+                if depth == 0 {
+                    return Ok(Some(buffer.coords(index)));
+                }
+
+                depth -= 1;
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn is_empty_line(buffer: &Buffer, y: usize) -> bool {
+    buffer.line_len(y) == Some(0)
+}
+
+/// Returns the last char of the buffer
+fn get_last_position(buffer: &Buffer) -> anyhow::Result<Coords> {
+    line_end(
+        buffer,
+        Coords {
+            x: 0,
+            y: buffer.len_lines() - 1,
+        },
+    )
+}
+
+/// `}`: the next empty line after the paragraph, or the end of the buffer
+pub fn next_paragraph(buffer: &Buffer, from: Coords) -> anyhow::Result<Coords> {
+    let last = buffer.len_lines() - 1;
+    let mut y = (from.y + 1).min(last);
+
+    // The empty lines the cursor is on are skipped, then the paragraph
+    while y < last && is_empty_line(buffer, y) && is_empty_line(buffer, from.y) {
+        y += 1;
+    }
+
+    while y < last && !is_empty_line(buffer, y) {
+        y += 1;
+    }
+
+    match is_empty_line(buffer, y) && y != from.y {
+        true => Ok(Coords { x: 0, y }),
+        false => get_last_position(buffer),
+    }
+}
+
+/// `{`: the previous empty line before the paragraph, or the start of the buffer
+pub fn prev_paragraph(buffer: &Buffer, from: Coords) -> anyhow::Result<Coords> {
+    let mut y = from.y.saturating_sub(1);
+
+    while y > 0 && is_empty_line(buffer, y) && is_empty_line(buffer, from.y) {
+        y -= 1;
+    }
+
+    while y > 0 && !is_empty_line(buffer, y) {
+        y -= 1;
+    }
+
+    Ok(Coords { x: 0, y })
+}
+
+/// Returns whether a sentence starts at the absolute char index `index`. Like in vim, sentences
+/// end with `.`, `!` or `?` followed by blanks, maybe with closing brackets and quotes in
+/// between, and empty lines are sentences of their own.
+fn is_sentence_start(buffer: &Buffer, index: usize) -> bool {
+    let mut chars = buffer.chars_at(index);
+
+    let Some(c) = chars.next() else {
+        return false;
+    };
+
+    chars.prev();
+
+    if c == '\n' {
+        return chars.prev().is_none_or(|previous| previous == '\n');
+    }
+
+    if c.is_whitespace() {
+        return false;
+    }
+
+    let mut blanks = false;
+    let mut previous = c;
+
+    loop {
+        let Some(before) = chars.prev() else {
+            return true;
+        };
+
+        match before {
+            '\n' if previous == '\n' => return true,
+            c if c.is_whitespace() => blanks = true,
+            _ if !blanks => return false,
+            mut before => {
+                while matches!(before, ')' | ']' | '"' | '\'') {
+                    match chars.prev() {
+                        Some(c) => before = c,
+                        None => return false,
+                    }
+                }
+
+                return matches!(before, '.' | '!' | '?');
+            }
+        }
+
+        previous = before;
+    }
+}
+
+/// `)`: the start of the next sentence, or the end of the buffer
+pub fn next_sentence(buffer: &Buffer, from: Coords) -> anyhow::Result<Coords> {
+    let start = get_index(buffer, from)?;
+    let len = buffer.char_index(get_last_position(buffer)?)? + 1;
+
+    match (start + 1..len).find(|index| is_sentence_start(buffer, *index)) {
+        Some(index) => Ok(buffer.coords(index)),
+        None => get_last_position(buffer),
+    }
+}
+
+/// `(`: the start of the current sentence, or of the previous one when already at its start
+pub fn prev_sentence(buffer: &Buffer, from: Coords) -> anyhow::Result<Coords> {
+    let start = get_index(buffer, from)?;
+
+    match (0..start)
+        .rev()
+        .find(|index| is_sentence_start(buffer, *index))
+    {
+        Some(index) => Ok(buffer.coords(index)),
+        None => Ok(Coords { x: 0, y: 0 }),
+    }
+}
+
+/// `iw`, `aw`, `iW` and `aW`: the word under the cursor, or the blanks under it. All of a word
+/// includes the blanks after it, or before it when there are none after, and all of some blanks
+/// includes the word after them.
+pub fn word_object(
+    buffer: &Buffer,
+    from: Coords,
+    big_word: bool,
+    around: bool,
+) -> anyhow::Result<Range<Coords>> {
+    let line = buffer
+        .line(from.y)
+        .ok_or(anyhow::anyhow!("No line at index {}", from.y))?;
+
+    // Zero width chars are in the class of the char they join
+    let classes = line
+        .chars()
+        .scan(CharClass::Blank, |last, c| {
+            if let Some(class) = get_class(c, big_word) {
+                *last = class;
+            }
+
+            Some(*last)
+        })
+        .collect::<Vec<_>>();
+
+    if classes.is_empty() {
+        return Ok(Coords { x: 0, y: from.y }..Coords { x: 0, y: from.y });
+    }
+
+    let len = classes.len();
+    let x = from.x.min(len - 1);
+    let class = classes[x];
+    let mut start = x;
+    let mut end = x + 1;
+
+    while start > 0 && classes[start - 1] == class {
+        start -= 1;
+    }
+
+    while end < len && classes[end] == class {
+        end += 1;
+    }
+
+    if around {
+        let after = classes.get(end).copied();
+
+        match (class, after) {
+            (CharClass::Blank, Some(after)) | (_, Some(after @ CharClass::Blank)) => {
+                while end < len && classes[end] == after {
+                    end += 1;
+                }
+            }
+            _ => {
+                while start > 0 && classes[start - 1] == CharClass::Blank {
+                    start -= 1;
+                }
+            }
+        }
+    }
+
+    Ok(Coords {
+        x: start,
+        y: from.y,
+    }..Coords { x: end, y: from.y })
+}
+
+/// `i(`, `a(`, `i{` and the like: the text inside the innermost `open` and `close` brackets
+/// around the cursor, or all of it with the brackets. Like in vim, a line break right after the
+/// opening bracket and the blanks before a closing bracket on a line of its own are left out of
+/// the inside, so the lines in between are selected whole.
+pub fn block_object(
+    buffer: &Buffer,
+    from: Coords,
+    open: char,
+    close: char,
+    around: bool,
+) -> anyhow::Result<Option<Range<Coords>>> {
+    let mut index = get_index(buffer, from)?;
+
+    let open_index = match buffer.chars_at(index).next() {
+        Some(c) if c == open => index,
+        _ => {
+            let mut chars = buffer.chars_at(index);
+            let mut depth = 0usize;
+
+            loop {
+                let Some(c) = chars.prev() else {
+                    return Ok(None);
+                };
+
+                index -= 1;
+
+                if c == close {
+                    depth += 1;
+                } else if c == open {
+                    match depth.checked_sub(1) {
+                        Some(outer) => depth = outer,
+                        None => break index,
+                    }
+                }
+            }
+        }
+    };
+
+    let open_at = buffer.coords(open_index);
+
+    let Some(close_at) = matching_bracket(buffer, open_at)? else {
+        return Ok(None);
+    };
+
+    let close_index = buffer.char_index(close_at)?;
+
+    if around {
+        return Ok(Some(open_at..buffer.coords(close_index + 1)));
+    }
+
+    let mut start = open_index + 1;
+    let mut end = close_index;
+
+    if buffer.chars_at(start).next() == Some('\n') {
+        start += 1;
+    }
+
+    let close_line_start = buffer.char_index(Coords {
+        x: 0,
+        y: close_at.y,
+    })?;
+
+    if close_at.y > open_at.y
+        && buffer
+            .chars_at(close_line_start)
+            .take(close_at.x)
+            .all(|c| c.is_whitespace())
+    {
+        end = close_line_start;
+    }
+
+    Ok(Some(buffer.coords(start)..buffer.coords(end.max(start))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Follows `motion` from `from` until it stops moving, returning every position
+    fn follow(
+        text: &str,
+        from: Coords,
+        motion: fn(&Buffer, Coords, bool) -> anyhow::Result<Coords>,
+        big_word: bool,
+    ) -> Vec<(usize, usize)> {
+        follow_by(text, from, |buffer, cursor| {
+            motion(buffer, cursor, big_word)
+        })
+    }
+
+    fn follow_by(
+        text: &str,
+        from: Coords,
+        motion: impl Fn(&Buffer, Coords) -> anyhow::Result<Coords>,
+    ) -> Vec<(usize, usize)> {
+        let buffer = Buffer::from(text);
+        let mut positions = vec![];
+        let mut cursor = from;
+
+        loop {
+            let next = motion(&buffer, cursor).unwrap();
+
+            if next == cursor {
+                return positions;
+            }
+
+            positions.push((next.x, next.y));
+            cursor = next;
+        }
+    }
+
+    #[test]
+    fn words_are_runs_of_the_same_class() {
+        let text = "let x = foo.bar(baz);";
+
+        assert_eq!(
+            follow(text, (0, 0).into(), next_word_start, false),
+            [
+                (4, 0),
+                (6, 0),
+                (8, 0),
+                (11, 0),
+                (12, 0),
+                (15, 0),
+                (16, 0),
+                (19, 0),
+                (20, 0)
+            ]
+        );
+        assert_eq!(
+            follow(text, (0, 0).into(), next_word_start, true),
+            [(4, 0), (6, 0), (8, 0), (20, 0)]
+        );
+    }
+
+    #[test]
+    fn word_motions_cross_lines_and_stop_on_empty_lines() {
+        let text = "one two\n\n  three four\nfive";
+
+        assert_eq!(
+            follow(text, (0, 0).into(), next_word_start, false),
+            [(4, 0), (0, 1), (2, 2), (8, 2), (0, 3), (3, 3)]
+        );
+        assert_eq!(
+            follow(text, (3, 3).into(), prev_word_start, false),
+            [(0, 3), (8, 2), (2, 2), (0, 1), (4, 0), (0, 0)]
+        );
+        assert_eq!(
+            follow(text, (0, 0).into(), next_word_end, false),
+            [(2, 0), (6, 0), (6, 2), (11, 2), (3, 3)]
+        );
+    }
+
+    #[test]
+    fn unicode_positions_are_char_indices() {
+        let text = "héllo wörld ñandú";
+
+        assert_eq!(
+            follow(text, (0, 0).into(), next_word_start, false),
+            [(6, 0), (12, 0), (16, 0)]
+        );
+        assert_eq!(
+            follow(text, (0, 0).into(), next_word_end, false),
+            [(4, 0), (10, 0), (16, 0)]
+        );
+    }
+
+    #[test]
+    fn combining_chars_belong_to_their_word() {
+        // Decomposed accents, which are zero width chars after the letter they go on
+        let text = "cafe\u{301} re\u{301}sume\u{301} x";
+
+        assert_eq!(
+            follow(text, (0, 0).into(), next_word_start, false),
+            [(6, 0), (15, 0)]
+        );
+        assert_eq!(
+            follow(text, (15, 0).into(), prev_word_start, false),
+            [(6, 0), (0, 0)]
+        );
+    }
+
+    #[test]
+    fn scripts_are_separate_words() {
+        let text = "日本語のテキストとtext";
+
+        assert_eq!(
+            follow(text, (0, 0).into(), next_word_start, false),
+            [(3, 0), (4, 0), (8, 0), (9, 0), (12, 0)]
+        );
+        assert_eq!(
+            follow(text, (0, 0).into(), next_word_start, true),
+            [(12, 0)]
+        );
+    }
+
+    #[test]
+    fn long_lines_span_several_chunks() {
+        let text = format!("{}end", "word ".repeat(400));
+        let buffer = Buffer::from(text.as_str());
+
+        assert!(buffer.chunks().count() > 1);
+        assert_eq!(
+            next_word_start(&buffer, (1995, 0).into(), false).unwrap(),
+            (2000, 0).into()
+        );
+        assert_eq!(
+            prev_word_start(&buffer, (2000, 0).into(), false).unwrap(),
+            (1995, 0).into()
+        );
+    }
+
+    #[test]
+    fn line_motions() {
+        let buffer = Buffer::from("  indented line\n\n   ");
+
+        assert_eq!(line_start((7, 0).into()), (0, 0).into());
+        assert_eq!(first_non_blank(&buffer, 0).unwrap(), (2, 0).into());
+        assert_eq!(first_non_blank(&buffer, 1).unwrap(), (0, 1).into());
+        assert_eq!(first_non_blank(&buffer, 2).unwrap(), (2, 2).into());
+        assert_eq!(line_end(&buffer, (0, 0).into()).unwrap(), (14, 0).into());
+        assert_eq!(line_end(&buffer, (0, 1).into()).unwrap(), (0, 1).into());
+        assert_eq!(goto_line(&buffer, 0).unwrap(), (2, 0).into());
+        assert_eq!(goto_line(&buffer, 99).unwrap(), (2, 2).into());
+
+        // Combining marks are part of the grapheme cluster before them
+        let buffer = Buffer::from("cafe\u{301}\n \u{301}x\n  \u{301}");
+
+        assert_eq!(line_end(&buffer, (0, 0).into()).unwrap(), (3, 0).into());
+        assert_eq!(first_non_blank(&buffer, 1).unwrap(), (0, 1).into());
+        assert_eq!(first_non_blank(&buffer, 2).unwrap(), (1, 2).into());
+    }
+
+    #[test]
+    fn char_searches_stay_in_the_line() {
+        let buffer = Buffer::from("a,b,c\n,");
+        let search = |c, forward, till| CharSearch { c, forward, till };
+
+        let find = |x, search, repeated| {
+            find_char(&buffer, (x, 0).into(), search, repeated)
+                .unwrap()
+                .map(|coords| (coords.x, coords.y))
+        };
+
+        assert_eq!(find(0, search(',', true, false), false), Some((1, 0)));
+        assert_eq!(find(1, search(',', true, false), false), Some((3, 0)));
+        assert_eq!(find(3, search(',', true, false), false), None);
+        assert_eq!(find(0, search(',', true, true), false), Some((0, 0)));
+        assert_eq!(find(0, search(',', true, true), true), Some((2, 0)));
+        assert_eq!(find(4, search(',', false, false), false), Some((3, 0)));
+        assert_eq!(find(4, search(',', false, true), false), Some((4, 0)));
+        assert_eq!(find(4, search(',', false, true), true), Some((2, 0)));
+        assert_eq!(find(4, search('a', false, false), false), Some((0, 0)));
+        assert_eq!(find(0, search('a', false, false), false), None);
+
+        // Searches stop at the start of grapheme clusters
+        let buffer = Buffer::from("ae\u{301}be\u{301}c");
+        let find = |x, search, repeated| {
+            find_char(&buffer, (x, 0).into(), search, repeated)
+                .unwrap()
+                .map(|coords| (coords.x, coords.y))
+        };
+
+        assert_eq!(find(0, search('e', true, false), false), Some((1, 0)));
+        assert_eq!(find(0, search('b', true, true), false), Some((1, 0)));
+        assert_eq!(find(1, search('e', true, true), true), Some((3, 0)));
+        assert_eq!(find(3, search('e', false, true), false), Some((3, 0)));
+        assert_eq!(find(6, search('e', false, true), false), Some((6, 0)));
+        assert_eq!(find(6, search('a', false, true), false), Some((1, 0)));
+        assert_eq!(find(6, search('e', false, false), false), Some((4, 0)));
+    }
+
+    #[test]
+    fn brackets_match_across_lines() {
+        let buffer = Buffer::from("if (a[0]) {\n    f(b);\n}");
+        let find = |x, y| {
+            matching_bracket(&buffer, (x, y).into())
+                .unwrap()
+                .map(|coords| (coords.x, coords.y))
+        };
+
+        assert_eq!(find(0, 0), Some((8, 0)));
+        assert_eq!(find(8, 0), Some((3, 0)));
+        assert_eq!(find(5, 0), Some((7, 0)));
+        assert_eq!(find(10, 0), Some((0, 2)));
+        assert_eq!(find(0, 2), Some((10, 0)));
+        assert_eq!(find(0, 1), Some((7, 1)));
+        assert_eq!(find(9, 1), None);
+        assert_eq!(
+            matching_bracket(&Buffer::from("(a"), (0, 0).into()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn paragraphs_are_separated_by_empty_lines() {
+        let text = "one\ntwo\n\n\nthree\n\nfour";
+
+        assert_eq!(
+            follow_by(text, (0, 0).into(), next_paragraph),
+            [(0, 2), (0, 5), (3, 6)]
+        );
+        assert_eq!(
+            follow_by(text, (3, 6).into(), prev_paragraph),
+            [(0, 5), (0, 3), (0, 0)]
+        );
+    }
+
+    #[test]
+    fn sentences_end_with_punctuation_and_blanks() {
+        let text = "One. Two!  (Three?) four\nfive.\n\nSix e.g.x";
+
+        assert_eq!(
+            follow_by(text, (0, 0).into(), next_sentence),
+            [(5, 0), (11, 0), (20, 0), (0, 2), (0, 3), (8, 3)]
+        );
+        assert_eq!(
+            follow_by(text, (8, 3).into(), prev_sentence),
+            [(0, 3), (0, 2), (20, 0), (11, 0), (5, 0), (0, 0)]
+        );
+        assert_eq!(
+            next_sentence(&Buffer::from("A.  B"), (2, 0).into()).unwrap(),
+            (4, 0).into()
+        );
+    }
+
+    #[test]
+    fn word_objects_take_blanks_after_or_before() {
+        let buffer = Buffer::from("foo  bar.baz");
+        let select = |x, around| {
+            let range = word_object(&buffer, (x, 0).into(), false, around).unwrap();
+
+            (range.start.x, range.end.x)
+        };
+
+        assert_eq!(select(1, false), (0, 3));
+        assert_eq!(select(1, true), (0, 5));
+        assert_eq!(select(3, false), (3, 5));
+        assert_eq!(select(3, true), (3, 8));
+        assert_eq!(select(10, false), (9, 12));
+        assert_eq!(select(10, true), (9, 12));
+        assert_eq!(select(6, true), (3, 8));
+    }
+
+    #[test]
+    fn block_objects_select_the_innermost_block() {
+        let buffer = Buffer::from("f(a, (b)) {\n    x;\n}");
+        let select = |x, y, open, close, around| {
+            block_object(&buffer, (x, y).into(), open, close, around)
+                .unwrap()
+                .map(|range| ((range.start.x, range.start.y), (range.end.x, range.end.y)))
+        };
+
+        assert_eq!(select(2, 0, '(', ')', false), Some(((2, 0), (8, 0))));
+        assert_eq!(select(6, 0, '(', ')', false), Some(((6, 0), (7, 0))));
+        assert_eq!(select(5, 0, '(', ')', true), Some(((5, 0), (8, 0))));
+        assert_eq!(select(8, 0, '(', ')', true), Some(((1, 0), (9, 0))));
+        assert_eq!(select(0, 0, '(', ')', false), None);
+        assert_eq!(select(2, 1, '{', '}', false), Some(((0, 1), (0, 2))));
+        assert_eq!(select(2, 1, '{', '}', true), Some(((10, 0), (1, 2))));
+    }
+}
@@ -0,0 +1,399 @@
+//! Normal mode commands, parsed from the keys typed so far. Like in vim, a command is an optional
+//! count followed by a motion, a simple command like `u`, or an operator with a count of its own
+//! and the motion or text object it applies to, such as `2d3w` or `ci(`.
+
+use std::{iter::Peekable, slice};
+
+use crate::core::motion::CharSearch;
+
+/// A cursor motion, which can also tell an operator which text it applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// `gj`, a screen row down, which is a line unless lines wrap
+    RowDown,
+    /// `gk`, a screen row up
+    RowUp,
+    WordStart {
+        big_word: bool,
+    },
+    PrevWordStart {
+        big_word: bool,
+    },
+    WordEnd {
+        big_word: bool,
+    },
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    /// `G`, to the line of the count or the last one
+    Line,
+    /// `gg`, to the line of the count or the first one
+    FirstLine,
+    FindChar(CharSearch),
+    /// `;`, or `,` which searches in the other direction
+    RepeatCharSearch {
+        reversed: bool,
+    },
+    /// `%`, or with a count the line that far through the buffer in percent
+    MatchingBracket,
+    NextParagraph,
+    PrevParagraph,
+    NextSentence,
+    PrevSentence,
+    ScreenTop,
+    ScreenMiddle,
+    ScreenBottom,
+}
+
+/// How much of the text between the cursor and the target of a motion an operator applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionKind {
+    /// Up to the target, which is left out
+    Exclusive,
+    /// Up to the target and including it
+    Inclusive,
+    /// Every line from the cursor to the target
+    Linewise,
+}
+
+impl Motion {
+    /// Returns the kind of the motion. `;` and `,` take the kind of the search they repeat,
+    /// which is up to the caller to look up.
+    pub fn get_kind(&self) -> MotionKind {
+        match self {
+            Motion::Up
+            | Motion::Down
+            | Motion::Line
+            | Motion::FirstLine
+            | Motion::ScreenTop
+            | Motion::ScreenMiddle
+            | Motion::ScreenBottom => MotionKind::Linewise,
+            Motion::WordEnd { .. } | Motion::LineEnd | Motion::MatchingBracket => {
+                MotionKind::Inclusive
+            }
+            Motion::FindChar(search) if search.forward => MotionKind::Inclusive,
+            _ => MotionKind::Exclusive,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+}
+
+/// A text object, selected with `i` for its inside or `a` for all of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextObject {
+    /// `iw`, `aw`, `iW` and `aW`, where all of it includes the blanks after the word
+    Word { big_word: bool, around: bool },
+    /// `i(`, `a[`, `iB` and the like, where all of it includes the brackets
+    Block {
+        open: char,
+        close: char,
+        around: bool,
+    },
+}
+
+/// The text an operator applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Motion(Motion),
+    TextObject(TextObject),
+    /// The operator typed twice, like `dd`, for whole lines
+    Line,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Move(Motion),
+    Operate(Operator, Target),
+    Insert,
+    Replace,
+    ReplaceChar(char),
+    Undo,
+    /// `<C-r>`, which isn't a char so it doesn't come from [`NormalCommand::parse`]
+    Redo,
+    /// `g-` and `g+`, which go through the history in chronological order
+    Travel {
+        forward: bool,
+    },
+    CommandLine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NormalCommand {
+    /// The counts typed before the command and before its motion multiplied together
+    pub count: Option<usize>,
+    pub action: Action,
+}
+
+/// The outcome of parsing the keys typed so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parse {
+    /// More keys are needed, like after `d` or `2g`
+    Pending,
+    /// The keys aren't a command, and are dropped like vim does
+    Invalid,
+    Complete(NormalCommand),
+}
+
+type Keys<'a> = Peekable<std::iter::Copied<slice::Iter<'a, char>>>;
+
+impl NormalCommand {
+    pub fn parse(keys: &[char]) -> Parse {
+        match parse_command(&mut keys.iter().copied().peekable()) {
+            Ok(command) => Parse::Complete(command),
+            Err(parse) => parse,
+        }
+    }
+
+    /// Returns the count when `keys` are only a count, which also applies to keys other than
+    /// chars, like `3<Down>`
+    pub fn parse_count(keys: &[char]) -> Option<usize> {
+        let mut keys = keys.iter().copied().peekable();
+        let count = parse_count(&mut keys);
+
+        keys.peek().is_none().then_some(count).flatten()
+    }
+
+    /// Returns the count, which is 1 when there is none
+    pub fn get_count(&self) -> usize {
+        self.count.unwrap_or(1)
+    }
+}
+
+fn parse_command(keys: &mut Keys) -> Result<NormalCommand, Parse> {
+    let count = parse_count(keys);
+
+    let action = match next_key(keys)? {
+        operator @ ('d' | 'c') => {
+            let operator_count = parse_count(keys);
+            let count = match (count, operator_count) {
+                (Some(count), Some(operator_count)) => Some(count.saturating_mul(operator_count)),
+                (count, operator_count) => count.or(operator_count),
+            };
+
+            let target = match next_key(keys)? {
+                key if key == operator => Target::Line,
+                key @ ('i' | 'a') => Target::TextObject(parse_text_object(key, next_key(keys)?)?),
+                key => Target::Motion(parse_motion(key, keys)?),
+            };
+
+            let operator = match operator {
+                'd' => Operator::Delete,
+                _ => Operator::Change,
+            };
+
+            return Ok(NormalCommand {
+                count,
+                action: Action::Operate(operator, target),
+            });
+        }
+        'i' => Action::Insert,
+        'R' => Action::Replace,
+        'r' => Action::ReplaceChar(next_key(keys)?),
+        'u' => Action::Undo,
+        ':' => Action::CommandLine,
+        'g' if matches!(keys.peek(), Some('-' | '+')) => Action::Travel {
+            forward: next_key(keys)? == '+',
+        },
+        key => Action::Move(parse_motion(key, keys)?),
+    };
+
+    Ok(NormalCommand { count, action })
+}
+
+fn next_key(keys: &mut Keys) -> Result<char, Parse> {
+    keys.next().ok_or(Parse::Pending)
+}
+
+/// Parses a count, which can't start with a 0 since that is the `0` motion
+fn parse_count(keys: &mut Keys) -> Option<usize> {
+    let mut count = None;
+
+    while let Some(digit) = keys
+        .peek()
+        .and_then(|key| key.to_digit(10))
+        .filter(|digit| *digit > 0 || count.is_some())
+    {
+        keys.next();
+
+        count = Some(
+            count
+                .unwrap_or(0usize)
+                .saturating_mul(10)
+                .saturating_add(digit as usize),
+        );
+    }
+
+    count
+}
+
+fn parse_motion(key: char, keys: &mut Keys) -> Result<Motion, Parse> {
+    let motion = match key {
+        'h' => Motion::Left,
+        'l' | ' ' => Motion::Right,
+        'k' => Motion::Up,
+        'j' => Motion::Down,
+        'w' | 'W' => Motion::WordStart {
+            big_word: key == 'W',
+        },
+        'b' | 'B' => Motion::PrevWordStart {
+            big_word: key == 'B',
+        },
+        'e' | 'E' => Motion::WordEnd {
+            big_word: key == 'E',
+        },
+        '0' => Motion::LineStart,
+        '^' => Motion::FirstNonBlank,
+        '$' => Motion::LineEnd,
+        'G' => Motion::Line,
+        'g' => match next_key(keys)? {
+            'g' => Motion::FirstLine,
+            'j' => Motion::RowDown,
+            'k' => Motion::RowUp,
+            _ => return Err(Parse::Invalid),
+        },
+        'f' | 'F' | 't' | 'T' => Motion::FindChar(CharSearch {
+            c: next_key(keys)?,
+            forward: key.is_ascii_lowercase(),
+            till: matches!(key, 't' | 'T'),
+        }),
+        ';' | ',' => Motion::RepeatCharSearch {
+            reversed: key == ',',
+        },
+        '%' => Motion::MatchingBracket,
+        '}' => Motion::NextParagraph,
+        '{' => Motion::PrevParagraph,
+        ')' => Motion::NextSentence,
+        '(' => Motion::PrevSentence,
+        'H' => Motion::ScreenTop,
+        'M' => Motion::ScreenMiddle,
+        'L' => Motion::ScreenBottom,
+        _ => return Err(Parse::Invalid),
+    };
+
+    Ok(motion)
+}
+
+fn parse_text_object(key: char, object: char) -> Result<TextObject, Parse> {
+    let around = key == 'a';
+
+    let (open, close) = match object {
+        'w' | 'W' => {
+            return Ok(TextObject::Word {
+                big_word: object == 'W',
+                around,
+            })
+        }
+        '(' | ')' | 'b' => ('(', ')'),
+        '[' | ']' => ('[', ']'),
+        '{' | '}' | 'B' => ('{', '}'),
+        _ => return Err(Parse::Invalid),
+    };
+
+    Ok(TextObject::Block {
+        open,
+        close,
+        around,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(keys: &str) -> Parse {
+        NormalCommand::parse(&keys.chars().collect::<Vec<_>>())
+    }
+
+    fn complete(count: Option<usize>, action: Action) -> Parse {
+        Parse::Complete(NormalCommand { count, action })
+    }
+
+    #[test]
+    fn counts_prefix_motions() {
+        assert_eq!(parse("j"), complete(None, Action::Move(Motion::Down)));
+        assert_eq!(parse("5"), Parse::Pending);
+        assert_eq!(parse("5j"), complete(Some(5), Action::Move(Motion::Down)));
+        assert_eq!(
+            parse("10w"),
+            complete(
+                Some(10),
+                Action::Move(Motion::WordStart { big_word: false })
+            )
+        );
+        assert_eq!(parse("0"), complete(None, Action::Move(Motion::LineStart)));
+    }
+
+    #[test]
+    fn counts_alone_are_parsed_for_other_keys() {
+        let count = |keys: &str| NormalCommand::parse_count(&keys.chars().collect::<Vec<_>>());
+
+        assert_eq!(count(""), None);
+        assert_eq!(count("3"), Some(3));
+        assert_eq!(count("12"), Some(12));
+        assert_eq!(count("0"), None);
+        assert_eq!(count("3d"), None);
+    }
+
+    #[test]
+    fn multi_key_sequences_wait_for_their_last_key() {
+        assert_eq!(parse("g"), Parse::Pending);
+        assert_eq!(parse("gg"), complete(None, Action::Move(Motion::FirstLine)));
+        assert_eq!(
+            parse("3gg"),
+            complete(Some(3), Action::Move(Motion::FirstLine))
+        );
+        assert_eq!(
+            parse("g-"),
+            complete(None, Action::Travel { forward: false })
+        );
+        assert_eq!(parse("gx"), Parse::Invalid);
+        assert_eq!(parse("f"), Parse::Pending);
+        assert_eq!(parse("r"), Parse::Pending);
+        assert_eq!(parse("rx"), complete(None, Action::ReplaceChar('x')));
+    }
+
+    #[test]
+    fn operators_take_a_motion_text_object_or_themselves() {
+        assert_eq!(parse("d"), Parse::Pending);
+        assert_eq!(
+            parse("dd"),
+            complete(None, Action::Operate(Operator::Delete, Target::Line))
+        );
+        assert_eq!(
+            parse("2d3w"),
+            complete(
+                Some(6),
+                Action::Operate(
+                    Operator::Delete,
+                    Target::Motion(Motion::WordStart { big_word: false })
+                )
+            )
+        );
+        assert_eq!(parse("ci"), Parse::Pending);
+        assert_eq!(
+            parse("ci("),
+            complete(
+                None,
+                Action::Operate(
+                    Operator::Change,
+                    Target::TextObject(TextObject::Block {
+                        open: '(',
+                        close: ')',
+                        around: false
+                    })
+                )
+            )
+        );
+        assert_eq!(parse("dc"), Parse::Invalid);
+        assert_eq!(parse("ciq"), Parse::Invalid);
+    }
+}
@@ -0,0 +1,214 @@
+use std::str::FromStr;
+
+use crate::core::command::CommandError;
+
+/// An option that can be changed with `:set`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionName {
+    FileFormat,
+    FileEncoding,
+    StatusLine,
+    Number,
+    RelativeNumber,
+    Wrap,
+    LineBreak,
+    ShowBreak,
+    TabStop,
+}
+
+impl OptionName {
+    /// Every option along with its full name and its abbreviations
+    const NAMES: &'static [(OptionName, &'static [&'static str])] = &[
+        (OptionName::FileFormat, &["fileformat", "ff"]),
+        (OptionName::FileEncoding, &["fileencoding", "fenc"]),
+        (OptionName::StatusLine, &["statusline", "stl"]),
+        (OptionName::Number, &["number", "nu"]),
+        (OptionName::RelativeNumber, &["relativenumber", "rnu"]),
+        (OptionName::Wrap, &["wrap"]),
+        (OptionName::LineBreak, &["linebreak", "lbr"]),
+        (OptionName::ShowBreak, &["showbreak", "sbr"]),
+        (OptionName::TabStop, &["tabstop", "ts"]),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(_, names)| names.contains(&name))
+            .map(|(option, _)| *option)
+    }
+
+    pub fn name(&self) -> &'static str {
+        Self::NAMES
+            .iter()
+            .find(|(option, _)| option == self)
+            .map(|(_, names)| names[0])
+            .unwrap_or_default()
+    }
+
+    pub fn is_boolean(&self) -> bool {
+        match self {
+            OptionName::FileFormat
+            | OptionName::FileEncoding
+            | OptionName::StatusLine
+            | OptionName::ShowBreak
+            | OptionName::TabStop => false,
+            OptionName::Number
+            | OptionName::RelativeNumber
+            | OptionName::Wrap
+            | OptionName::LineBreak => true,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SetAction {
+    Enable,
+    Disable,
+    Toggle,
+    Query,
+    Assign(String),
+}
+
+/// A single argument of `:set`, such as `number`, `nonumber`, `number!`, `number?` or
+/// `fileformat=dos`
+#[derive(Debug, PartialEq, Eq)]
+pub struct SetArgument {
+    pub option: OptionName,
+    pub action: SetAction,
+}
+
+impl FromStr for SetArgument {
+    type Err = CommandError;
+
+    fn from_str(argument: &str) -> Result<Self, Self::Err> {
+        if let Some((name, value)) = argument.split_once('=') {
+            // vim's `+=`, `-=` and `^=` aren't supported, but name the option as it was meant
+            let operator = name.ends_with(['+', '-', '^']);
+            let option = parse_option(name.trim_end_matches(['+', '-', '^']))?;
+
+            if option.is_boolean() || operator {
+                return Err(CommandError::InvalidArgument(argument.to_owned()));
+            }
+
+            return Ok(SetArgument {
+                option,
+                action: SetAction::Assign(value.to_owned()),
+            });
+        }
+
+        if let Some(name) = argument.strip_suffix('?') {
+            return Ok(SetArgument {
+                option: parse_option(name)?,
+                action: SetAction::Query,
+            });
+        }
+
+        if let Some(option) = OptionName::from_name(argument) {
+            let action = match option.is_boolean() {
+                true => SetAction::Enable,
+                false => SetAction::Query,
+            };
+
+            return Ok(SetArgument { option, action });
+        }
+
+        let (name, action) = if let Some(name) = argument.strip_suffix('!') {
+            (name, SetAction::Toggle)
+        } else if let Some(name) = argument.strip_prefix("inv") {
+            (name, SetAction::Toggle)
+        } else if let Some(name) = argument.strip_prefix("no") {
+            (name, SetAction::Disable)
+        } else {
+            return Err(CommandError::UnknownOption(argument.to_owned()));
+        };
+
+        let option = parse_option(name)?;
+
+        if !option.is_boolean() {
+            return Err(CommandError::InvalidArgument(argument.to_owned()));
+        }
+
+        Ok(SetArgument { option, action })
+    }
+}
+
+fn parse_option(name: &str) -> Result<OptionName, CommandError> {
+    OptionName::from_name(name).ok_or(CommandError::UnknownOption(name.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argument(option: OptionName, action: SetAction) -> Result<SetArgument, CommandError> {
+        Ok(SetArgument { option, action })
+    }
+
+    #[test]
+    fn boolean_options_are_enabled_disabled_or_toggled() {
+        for (input, action) in [
+            ("nu", SetAction::Enable),
+            ("number", SetAction::Enable),
+            ("nonu", SetAction::Disable),
+            ("nonumber", SetAction::Disable),
+            ("nu!", SetAction::Toggle),
+            ("invnu", SetAction::Toggle),
+            ("nu?", SetAction::Query),
+        ] {
+            assert_eq!(
+                input.parse(),
+                argument(OptionName::Number, action),
+                "{}",
+                input
+            );
+        }
+
+        assert_eq!(
+            "nowrap".parse(),
+            argument(OptionName::Wrap, SetAction::Disable)
+        );
+    }
+
+    #[test]
+    fn other_options_are_assigned_or_queried() {
+        assert_eq!(
+            "ts=4".parse(),
+            argument(OptionName::TabStop, SetAction::Assign("4".to_owned()))
+        );
+        assert_eq!(
+            "stl=%f=%l".parse(),
+            argument(
+                OptionName::StatusLine,
+                SetAction::Assign("%f=%l".to_owned())
+            )
+        );
+        assert_eq!(
+            "sbr=".parse(),
+            argument(OptionName::ShowBreak, SetAction::Assign("".to_owned()))
+        );
+        assert_eq!(
+            "ts".parse(),
+            argument(OptionName::TabStop, SetAction::Query)
+        );
+        assert_eq!(
+            "ff?".parse(),
+            argument(OptionName::FileFormat, SetAction::Query)
+        );
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        for (input, error) in [
+            ("foo", CommandError::UnknownOption("foo".to_owned())),
+            ("foo=1", CommandError::UnknownOption("foo".to_owned())),
+            ("nofoo", CommandError::UnknownOption("foo".to_owned())),
+            ("foo?", CommandError::UnknownOption("foo".to_owned())),
+            ("nu=1", CommandError::InvalidArgument("nu=1".to_owned())),
+            ("nots", CommandError::InvalidArgument("nots".to_owned())),
+            ("ts!", CommandError::InvalidArgument("ts!".to_owned())),
+            ("ts+=2", CommandError::InvalidArgument("ts+=2".to_owned())),
+        ] {
+            assert_eq!(input.parse::<SetArgument>(), Err(error), "{}", input);
+        }
+    }
+}
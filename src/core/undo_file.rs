@@ -0,0 +1,151 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{document, History};
+
+/// The on-disk format of a persisted undo history. `hash` is the hash of the file content the
+/// history was saved for, so a file changed outside of chai doesn't get a stale history.
+#[derive(Serialize, Deserialize)]
+struct UndoFile {
+    hash: u64,
+    history: History,
+}
+
+/// Hashes file content with 64-bit FNV-1a, which is stable across builds unlike `DefaultHasher`
+pub fn hash_content(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Loads the undo history saved for `file_path`, if there is one and it was saved for content
+/// with the same hash
+pub async fn load(file_path: &Path, hash: u64) -> Option<History> {
+    load_from(&get_cache_dir().ok()?.join("undo"), file_path, hash).await
+}
+
+pub async fn save(file_path: &Path, hash: u64, history: &History) -> io::Result<()> {
+    save_to(&get_cache_dir()?.join("undo"), file_path, hash, history).await
+}
+
+async fn load_from(undo_dir: &Path, file_path: &Path, hash: u64) -> Option<History> {
+    let undo_file_path = get_undo_file_path(undo_dir, file_path).await.ok()?;
+    let bytes = tokio::fs::read(undo_file_path).await.ok()?;
+    let undo_file = serde_json::from_slice::<UndoFile>(&bytes).ok()?;
+
+    if undo_file.hash != hash || !undo_file.history.is_valid() {
+        return None;
+    }
+
+    Some(undo_file.history)
+}
+
+async fn save_to(
+    undo_dir: &Path,
+    file_path: &Path,
+    hash: u64,
+    history: &History,
+) -> io::Result<()> {
+    let undo_file_path = get_undo_file_path(undo_dir, file_path).await?;
+
+    if let Some(parent) = undo_file_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let undo_file = UndoFile {
+        hash,
+        history: history.clone(),
+    };
+
+    // Another instance may read it at the same time, and a truncated file would be discarded
+    document::write_atomic(&undo_file_path, &serde_json::to_vec(&undo_file)?).await
+}
+
+/// Returns where in `undo_dir` the undo history of `file_path` is stored. Like vim, the canonical
+/// path is turned into a flat file name by replacing the path separators with `%`.
+async fn get_undo_file_path(undo_dir: &Path, file_path: &Path) -> io::Result<PathBuf> {
+    let canonical_path = tokio::fs::canonicalize(file_path).await?;
+    let file_name = canonical_path
+        .to_string_lossy()
+        .replace(std::path::MAIN_SEPARATOR, "%");
+
+    Ok(undo_dir.join(file_name))
+}
+
+fn get_cache_dir() -> io::Result<PathBuf> {
+    if let Some(cache_dir) = std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(cache_dir).join("chai"));
+    }
+
+    let home = std::env::var_os("HOME").ok_or(io::Error::new(
+        io::ErrorKind::NotFound,
+        "Could not find the cache directory",
+    ))?;
+
+    Ok(PathBuf::from(home).join(".cache").join("chai"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{history::Edit, Coords},
+        testing::TempDir,
+    };
+
+    fn history() -> History {
+        let mut history = History::new();
+
+        history.record(
+            Edit::Insert {
+                at: Coords { x: 0, y: 0 },
+                text: "text".to_owned(),
+            },
+            Coords { x: 0, y: 0 },
+        );
+        history.commit();
+
+        history
+    }
+
+    #[tokio::test]
+    async fn saved_history_loads_back() {
+        let dir = TempDir::new("undo-round-trip");
+        let file_path = PathBuf::from(dir.create_file("file.txt", "text"));
+        let undo_dir = dir.get_path().join("undo");
+        let hash = hash_content(b"text");
+
+        save_to(&undo_dir, &file_path, hash, &history())
+            .await
+            .unwrap();
+
+        let loaded = load_from(&undo_dir, &file_path, hash).await.unwrap();
+
+        // Only the undo file is left, without the temporary file it was written to
+        assert_eq!(std::fs::read_dir(&undo_dir).unwrap().count(), 1);
+
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(history()).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn history_of_other_content_is_rejected() {
+        let dir = TempDir::new("undo-stale");
+        let file_path = PathBuf::from(dir.create_file("file.txt", "text"));
+        let undo_dir = dir.get_path().join("undo");
+
+        save_to(&undo_dir, &file_path, hash_content(b"text"), &history())
+            .await
+            .unwrap();
+
+        let loaded = load_from(&undo_dir, &file_path, hash_content(b"changed")).await;
+
+        assert!(loaded.is_none());
+    }
+}
@@ -1,21 +1,48 @@
-use chai::TermSize;
-use crossterm::{event::EventStream, terminal::window_size};
+use std::io::{self, IsTerminal};
 
+use backend::TerminalBackend;
+use cli::{Action, FileArg};
+use crossterm::event::EventStream;
+
+mod backend;
 mod chai;
+mod cli;
 mod components;
 mod core;
+#[cfg(test)]
+mod testing;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let file_path = std::env::args().nth(1);
-    let size = window_size()?;
+    let mut args = match cli::parse(std::env::args().skip(1)) {
+        Ok(Action::Edit(args)) => args,
+        Ok(Action::Help) => {
+            print!("{}", cli::HELP);
+
+            return Ok(());
+        }
+        Ok(Action::Version) => {
+            println!("chai {}", env!("CARGO_PKG_VERSION"));
 
-    let size = TermSize {
-        width: size.width,
-        height: size.height,
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("chai: {}\nMore info with: chai --help", e);
+
+            std::process::exit(1);
+        }
     };
 
-    chai::Chai::new(file_path, size)
+    // Read piped input even without `-`, like `cargo tree | chai`. crossterm reads the keyboard
+    // from the controlling terminal when stdin isn't one, so the editor stays interactive.
+    if args.files.is_empty() && !io::stdin().is_terminal() {
+        args.files.push(FileArg {
+            file_path: "-".to_owned(),
+            cursor: None,
+        });
+    }
+
+    chai::Chai::new(args, TerminalBackend::new())
         .await?
         .start(&mut EventStream::new())
         .await
@@ -0,0 +1,228 @@
+//! Helpers to run the editor headlessly in tests, driven by keys written like vim mappings such
+//! as `"iHello<Esc>bx"`, and to compare the outcome with golden snapshots.
+//!
+//! Snapshots are stored in `src/snapshots`. Run the tests with `UPDATE_SNAPSHOTS=1` to write
+//! new snapshots or update them after an intended change; otherwise a missing snapshot fails the
+//! test like a mismatching one.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+use futures_util::stream;
+
+use crate::{
+    backend::TestBackend,
+    chai::Chai,
+    cli::Args,
+    components::{command_line::Message, TextBlock},
+    core::Document,
+};
+
+pub const WIDTH: u16 = 41;
+pub const HEIGHT: u16 = 8;
+
+/// Parses keys written like vim mappings: plain chars stand for themselves and special keys are
+/// written between angle brackets, like `<Esc>`, `<CR>`, `<BS>`, `<C-r>` or `<lt>` for `<`
+pub fn parse_keys(keys: &str) -> Vec<io::Result<Event>> {
+    let mut events = vec![];
+    let mut rest = keys;
+
+    while let Some(c) = rest.chars().next() {
+        let special = rest
+            .strip_prefix('<')
+            .and_then(|special| special.split_once('>'))
+            .filter(|(name, _)| !name.is_empty() && !name.contains('<'));
+
+        let (modifiers, code) = match special {
+            Some((name, after)) => {
+                rest = after;
+
+                parse_special_key(name)
+            }
+            None => {
+                rest = &rest[c.len_utf8()..];
+
+                match c.is_uppercase() {
+                    true => (KeyModifiers::SHIFT, KeyCode::Char(c)),
+                    false => (KeyModifiers::NONE, KeyCode::Char(c)),
+                }
+            }
+        };
+
+        events.push(Ok(Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        })));
+    }
+
+    events
+}
+
+fn parse_special_key(name: &str) -> (KeyModifiers, KeyCode) {
+    if let Some(c) = name
+        .strip_prefix("C-")
+        .and_then(|key| key.chars().next().filter(|_| key.chars().count() == 1))
+    {
+        return (KeyModifiers::CONTROL, KeyCode::Char(c));
+    }
+
+    let code = match name {
+        "Esc" => KeyCode::Esc,
+        "CR" | "Enter" => KeyCode::Enter,
+        "BS" => KeyCode::Backspace,
+        "Del" => KeyCode::Delete,
+        "Tab" => KeyCode::Tab,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "Space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        _ => panic!("Unknown key: <{}>", name),
+    };
+
+    (KeyModifiers::NONE, code)
+}
+
+/// A directory in the temporary directory, removed along with its content when dropped
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates an empty directory, `name` keeping it apart from the ones of other tests
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("chai-{}-{}", std::process::id(), name));
+
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        Self { path }
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Creates a file with `content` in the directory, returning its path
+    pub fn create_file(&self, name: &str, content: &str) -> String {
+        let path = self.path.join(name);
+
+        std::fs::write(&path, content).unwrap();
+
+        path.to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Runs the editor with `args` until every key of `keys` was handled
+pub async fn run_with_args(args: Args, keys: &str) -> Chai<TestBackend> {
+    let mut editor = Chai::new(args, TestBackend::new(WIDTH, HEIGHT))
+        .await
+        .unwrap();
+
+    editor
+        .start(&mut stream::iter(parse_keys(keys)))
+        .await
+        .unwrap();
+
+    editor
+}
+
+/// Runs the editor on an unnamed document holding `content` until every key of `keys` was
+/// handled
+pub async fn run(content: &str, keys: &str) -> Chai<TestBackend> {
+    let mut editor = Chai::new(Args::default(), TestBackend::new(WIDTH, HEIGHT))
+        .await
+        .unwrap();
+
+    editor.documents[0].replace(Document::from(content));
+
+    editor
+        .start(&mut stream::iter(parse_keys(keys)))
+        .await
+        .unwrap();
+
+    editor
+}
+
+pub fn get_active_view(editor: &Chai<TestBackend>) -> &TextBlock {
+    editor.panes[editor.active_pane_index]
+        .get_active_view()
+        .unwrap()
+}
+
+/// Returns the text of the document shown in the active view
+pub fn get_text(editor: &Chai<TestBackend>) -> String {
+    get_active_view(editor)
+        .get_document()
+        .borrow()
+        .get_content()
+        .to_string()
+}
+
+/// Returns the text of the message shown in the command line
+pub fn get_message(editor: &Chai<TestBackend>) -> Option<&str> {
+    match editor.command_line.get_message()? {
+        Message::Info(message) | Message::Error(message) => Some(message),
+    }
+}
+
+/// Compares the text, the cursor and the screen of `editor` with the snapshot called `name`
+pub fn assert_snapshot(name: &str, keys: &str, editor: &Chai<TestBackend>) {
+    let (x, y) = get_active_view(editor).get_cursor();
+    let screen = editor
+        .backend
+        .get_lines()
+        .iter()
+        .map(|line| format!("|{}", line))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let snapshot = format!(
+        "keys: {}\ncursor: {}, {}\n--- text\n{}\n--- screen\n{}\n",
+        keys,
+        x,
+        y,
+        get_text(editor),
+        screen
+    );
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/snapshots")
+        .join(format!("{}.snap", name));
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, snapshot).unwrap();
+
+        return;
+    }
+
+    match std::fs::read_to_string(&path).ok() {
+        Some(expected) => assert!(
+            expected == snapshot,
+            "Snapshot {} doesn't match, run with UPDATE_SNAPSHOTS=1 to update it\n\
+             --- expected\n{}--- actual\n{}",
+            name,
+            expected,
+            snapshot
+        ),
+        None => panic!(
+            "Snapshot {} is missing, run with UPDATE_SNAPSHOTS=1 to create it\n{}",
+            name, snapshot
+        ),
+    }
+}